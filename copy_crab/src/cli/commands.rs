@@ -0,0 +1,387 @@
+use crate::models::{
+    ChosenFeatures, Feature, FileDrift, FileState, HandEdits, Modularity,
+    PlannedChange, ProjectBuilder, ProjectChoices,
+};
+use crate::error::{CopyCrabError, Result};
+use crate::output::{self, Event};
+use crate::settings_finder::{self, ConfigHost};
+use super::InitArgs;
+
+use std::io::IsTerminal;
+
+use colored::Colorize;
+use inquire::Confirm;
+
+pub fn init(args: InitArgs, installation: Option<&str>, dry_run: bool) -> Result<()> {
+    let settings = settings_finder::find_settings()?.unwrap_or_default();
+    let defaults = settings_finder::user_defaults()?;
+
+    let runtime = args.runtime
+        .or(defaults.runtime)
+        .ok_or_else(|| missing_option("--runtime"))?;
+
+    // The default directory is already relative to the project root
+    let dir = args.dir
+        .map(|dir| settings_finder::resolve_path(&dir))
+        .or(defaults.chosen_directory)
+        .ok_or_else(|| missing_option("--dir"))?;
+    if !std::fs::metadata(&dir).is_ok_and(|meta| meta.is_dir()) {
+        return Err(CopyCrabError::InvalidCombination(format!("{} is not an existing directory", dir)));
+    }
+
+    let feature_set = if !args.features.is_empty() {
+        ChosenFeatures::Custom { features: in_declared_order(&args.features) }
+    } else {
+        let preset_name = args.preset
+            .or(defaults.preset)
+            .ok_or_else(|| missing_option("--preset or --features"))?;
+        if !preset_name.exists() {
+            return Err(CopyCrabError::InvalidCombination(format!("There is no preset called {preset_name}")));
+        }
+        ChosenFeatures::Preset { preset_name }
+    };
+
+    let modularity = if args.split {
+        Modularity::SplitFiles
+    } else if args.single_file {
+        Modularity::SingleFile
+    } else {
+        defaults.modularity.unwrap_or(Modularity::SingleFile)
+    };
+
+    let mut builder = ProjectBuilder::new();
+    if let Some(name) = installation {
+        builder = builder.set_name(name.to_string());
+    }
+
+    let choices = builder
+        .set_runtime(runtime)
+        .set_chosen_dir(dir)
+        .set_feature_set(feature_set)
+        .set_modularity(modularity)
+        .build()?;
+
+    settings.check_new(&choices)?;
+    announce_dependencies(&[], &choices);
+    apply(&choices, dry_run, HandEdits::Keep)
+}
+
+/// An `init` option that was neither passed nor set in the user-level config
+fn missing_option(option: &str) -> CopyCrabError {
+    let config_path = settings_finder::user_defaults_path()
+        .map(|path| path.display().to_string())
+        .unwrap_or_else(|| "the user-level config".to_string());
+
+    CopyCrabError::InvalidCombination(format!("{option} is required, unless a default is set in {config_path}"))
+}
+
+pub fn add(requested: Vec<Feature>, installation: Option<&str>, dry_run: bool, force: bool) -> Result<()> {
+    let mut choices = load_settings(installation)?;
+    let installed = choices.features();
+
+    let already_installed: Vec<&Feature> = requested
+        .iter()
+        .filter(|feature| installed.contains(feature))
+        .collect();
+    for feature in &already_installed {
+        output::emit(Event::Warning { message: format!("{} is already installed, skipping", feature.display_name()) });
+    }
+
+    if already_installed.len() == requested.len() {
+        output::info("Nothing to add".truecolor(0, 220, 150));
+        return Ok(());
+    }
+
+    for feature in requested.iter().filter(|feature| !choices.supports(feature)) {
+        output::emit(Event::Warning {
+            message: format!(
+                "{} doesn't support {:?}, so it's recorded but no code is written for it",
+                feature.display_name(),
+                choices.runtime
+            ),
+        });
+    }
+
+    let chosen = choices.feature_set.get_feature_list();
+    let features = in_declared_order(&[chosen, requested].concat());
+    choices.feature_set = ChosenFeatures::Custom { features };
+    announce_dependencies(&installed, &choices);
+    apply_confirmed(&choices, dry_run, force)
+}
+
+pub fn remove(requested: Vec<Feature>, installation: Option<&str>, dry_run: bool, force: bool) -> Result<()> {
+    let choices = load_settings(installation)?;
+    let installed = choices.features();
+
+    if let Some(missing) = requested.iter().find(|feature| !installed.contains(feature)) {
+        return Err(CopyCrabError::Conflict(format!("{} is not installed in this project", missing.display_name())));
+    }
+
+    // What the chosen features need goes away together with them
+    if choices.feature_set.get_feature_list().iter().all(|feature| requested.contains(feature)) {
+        // It doesn't make sense to have an packageless version of this
+        return Err(CopyCrabError::InvalidCombination(
+            "Removing every feature would leave nothing installed. Use `copy_crab uninstall` instead".to_string()
+        ));
+    }
+
+    apply_confirmed(&choices.without(&requested)?, dry_run, force)
+}
+
+/// Explains whether `feature` was chosen or is only there because other features need it
+pub fn why(feature: Feature, installation: Option<&str>) -> Result<()> {
+    let choices = load_settings(installation)?;
+    if !choices.features().contains(&feature) {
+        return Err(CopyCrabError::Conflict(format!("{} is not installed in this project", feature.display_name())));
+    }
+
+    output::emit(Event::Why {
+        installation: choices.name.clone(),
+        chosen: choices.feature_set.get_feature_list().contains(&feature),
+        needed_by: choices.needed_by(&feature),
+        feature,
+    });
+
+    Ok(())
+}
+
+/// Tells which features come along because the new choices depend on them
+fn announce_dependencies(installed: &[Feature], choices: &ProjectChoices) {
+    let chosen = choices.feature_set.get_feature_list();
+    for feature in choices.features() {
+        if chosen.contains(&feature) || installed.contains(&feature) {
+            continue;
+        }
+
+        output::emit(Event::DependencyAdded {
+            installation: choices.name.clone(),
+            needed_by: choices.needed_by(&feature),
+            feature,
+        });
+    }
+}
+
+pub fn status(installation: Option<&str>) -> Result<()> {
+    for choices in load_all(installation)? {
+        print_status(&choices)?;
+    }
+
+    Ok(())
+}
+
+pub fn print_status(choices: &ProjectChoices) -> Result<()> {
+    let preset = match &choices.feature_set {
+        ChosenFeatures::Preset { preset_name } => Some(preset_name.clone()),
+        ChosenFeatures::Custom { .. } => None,
+    };
+
+    let extra_files = choices.find_drift()?
+        .into_iter()
+        .filter(|file| file.state == FileState::Extra)
+        .map(|file| file.path)
+        .collect();
+
+    output::emit(Event::Status {
+        installation: choices.name.clone(),
+        runtime: choices.runtime.clone(),
+        chosen_directory: choices.chosen_directory.clone(),
+        modularity: choices.modularity.clone(),
+        preset,
+        features: choices.feature_status(),
+        extra_files,
+    });
+
+    Ok(())
+}
+
+pub fn check(installation: Option<&str>) -> Result<()> {
+    let mut drift_count = 0;
+    for choices in load_all(installation)? {
+        drift_count += check_installation(&choices)?;
+    }
+
+    if drift_count == 0 {
+        output::info("All crabSafe files match the configuration".truecolor(0, 220, 150));
+        return Ok(());
+    }
+
+    Err(CopyCrabError::Drift(drift_count))
+}
+
+/// Reports the files of `choices` that drifted and returns how many there are
+fn check_installation(choices: &ProjectChoices) -> Result<usize> {
+    let drift: Vec<FileDrift> = choices.find_drift()?
+        .into_iter()
+        .filter(|file| file.state != FileState::UpToDate)
+        .collect();
+
+    for file in &drift {
+        output::emit(Event::Drift(file.clone()));
+    }
+
+    // Show how the changed files differ from what copy_crab would write
+    let expected_files = choices.expected_files()?;
+    let stale_files = expected_files
+        .iter()
+        .filter(|file| drift.iter().any(|d| d.path == file.path && d.state.is_changed()));
+    for file in stale_files {
+        let change = PlannedChange::write(file.path.clone(), file.contents.clone());
+        output::emit(Event::planned(&change));
+    }
+
+    Ok(drift.len())
+}
+
+pub fn sync(installation: Option<&str>, dry_run: bool) -> Result<()> {
+    for choices in load_all(installation)? {
+        let drift = choices.find_drift()?;
+
+        for file in &drift {
+            output::emit(Event::Drift(file.clone()));
+        }
+
+        if drift.iter().all(|file| file.state == FileState::UpToDate) {
+            output::info(format!("{} is already in sync", choices.name).truecolor(0, 220, 150));
+            continue;
+        }
+
+        apply(&choices, dry_run, HandEdits::Restore)?;
+    }
+
+    Ok(())
+}
+
+pub fn uninstall(yes: bool, installation: Option<&str>, dry_run: bool) -> Result<()> {
+    let choices = load_settings(installation)?;
+
+    if dry_run {
+        show_plan(&settings_finder::plan_removal(&choices)?);
+        return Ok(());
+    }
+
+    if !yes && output::is_json() {
+        return Err(CopyCrabError::InvalidCombination(
+            "Pass `--yes` to uninstall in JSON mode, there is no one to confirm it".to_string()
+        ));
+    }
+
+    if !yes {
+        let message = format!(
+            "{} {}",
+            "WARN:".black().on_red(),
+            "This will remove the entire crabSafe implementation. Continue?"
+        );
+        let ans = Confirm::new(&message)
+            .with_default(false)
+            .prompt()?;
+
+        if !ans {
+            output::info("Uninstall cancelled");
+            return Ok(());
+        }
+    }
+
+    settings_finder::remove_completely(&choices)
+}
+
+pub fn move_config(to: ConfigHost, dry_run: bool) -> Result<()> {
+    let changes = settings_finder::plan_move(to)?;
+
+    if dry_run {
+        show_plan(&changes);
+        return Ok(());
+    }
+
+    // The moved settings are saved with the current schema
+    settings_finder::backup_outdated()?;
+    for change in &changes {
+        change.apply()?;
+        output::emit(Event::applied(change));
+    }
+
+    Ok(())
+}
+
+/// Like `apply`, but files edited by hand that would be overwritten need
+/// `--force` or the user's consent. In JSON mode or without a terminal there
+/// is no one to ask
+fn apply_confirmed(choices: &ProjectChoices, dry_run: bool, force: bool) -> Result<()> {
+    let edited_files = choices.overwritten_edits()?;
+    if force || dry_run || edited_files.is_empty() {
+        return apply(choices, dry_run, HandEdits::OverwriteChanged);
+    }
+
+    if output::is_json() || !std::io::stdin().is_terminal() {
+        return apply(choices, dry_run, HandEdits::Keep);
+    }
+
+    let message = format!(
+        "{} {} {}. Overwrite {}?",
+        "WARN:".black().on_yellow(),
+        "Edited by hand:",
+        edited_files.join(", "),
+        if edited_files.len() == 1 { "it" } else { "them" },
+    );
+    let ans = Confirm::new(&message)
+        .with_default(false)
+        .prompt()?;
+
+    if !ans {
+        output::info("Nothing was changed");
+        return Ok(());
+    }
+
+    apply(choices, dry_run, HandEdits::OverwriteChanged)
+}
+
+/// Writes the installation described by `choices`, or only shows the diff
+fn apply(choices: &ProjectChoices, dry_run: bool, edits: HandEdits) -> Result<()> {
+    if dry_run {
+        show_plan(&choices.plan(edits)?);
+        return Ok(());
+    }
+
+    for change in choices.handle(edits)? {
+        output::emit(Event::applied(&change));
+    }
+
+    Ok(())
+}
+
+fn show_plan(changes: &[PlannedChange]) {
+    if changes.is_empty() {
+        output::info("No changes".truecolor(0, 220, 150));
+    }
+
+    for change in changes {
+        output::emit(Event::planned(change));
+    }
+}
+
+fn load_settings(installation: Option<&str>) -> Result<ProjectChoices> {
+    settings_finder::find_settings()?
+        .ok_or(CopyCrabError::NotInstalled)?
+        .select(installation)
+        .cloned()
+}
+
+/// The installation called `installation`, or every installation without a name
+fn load_all(installation: Option<&str>) -> Result<Vec<ProjectChoices>> {
+    let settings = settings_finder::find_settings()?
+        .ok_or(CopyCrabError::NotInstalled)?;
+
+    match installation {
+        Some(_) => Ok(vec![settings.select(installation)?.clone()]),
+        None if settings.installations.is_empty() => Err(CopyCrabError::NotInstalled),
+        None => Ok(settings.installations),
+    }
+}
+
+/// Deduplicates `features` and sorts them the way they are declared in `Feature`,
+/// so the generated output doesn't depend on the order they were asked for
+fn in_declared_order(features: &[Feature]) -> Vec<Feature> {
+    Feature::get_all()
+        .into_iter()
+        .filter(|feature| features.contains(feature))
+        .collect()
+}
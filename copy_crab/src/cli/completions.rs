@@ -0,0 +1,139 @@
+use std::io::{self, Write};
+
+use crate::error::{IoContext, Result};
+use crate::models::{Feature, FeatureSet, ProjectChoices, Settings};
+use crate::settings_finder;
+
+use clap::{CommandFactory, ValueEnum};
+use clap_complete::env::{self, EnvCompleter};
+use clap_complete::CompletionCandidate;
+
+use super::Cli;
+
+const BIN_NAME: &str = "copy_crab";
+
+// The variable the generated scripts set when asking copy_crab for candidates
+const COMPLETE_VAR: &str = "COMPLETE";
+
+#[derive(Clone, Copy, ValueEnum)]
+pub enum Shell {
+    Bash,
+    Zsh,
+    Fish,
+    Powershell,
+}
+
+/// Prints a script that hooks copy_crab into `shell`. The script calls back
+/// into copy_crab, so candidates can depend on the current project
+pub fn print_completions(shell: Shell) -> Result<()> {
+    let completer: &dyn EnvCompleter = match shell {
+        Shell::Bash => &env::Bash,
+        Shell::Zsh => &env::Zsh,
+        Shell::Fish => &env::Fish,
+        Shell::Powershell => &env::Powershell,
+    };
+
+    let mut stdout = io::stdout().lock();
+    completer
+        .write_registration(COMPLETE_VAR, BIN_NAME, BIN_NAME, BIN_NAME, &mut stdout)
+        .and_then(|_| stdout.flush())
+        .at("stdout")
+}
+
+/// Prints the man page in roff format
+pub fn print_man_page() -> Result<()> {
+    let man = clap_mangen::Man::new(Cli::command());
+    man.render(&mut io::stdout().lock()).at("stdout")
+}
+
+/// Answers the requests made by the scripts from `print_completions`.
+/// Exits when it handled one, so this has to run before anything is printed
+pub fn complete_if_requested() {
+    clap_complete::CompleteEnv::with_factory(Cli::command)
+        .var(COMPLETE_VAR)
+        .bin(BIN_NAME)
+        .complete();
+}
+
+/// Candidates for `add`
+pub fn uninstalled_features() -> Vec<CompletionCandidate> {
+    let installed = installed();
+    candidates(Feature::get_all().into_iter().filter(|feature| !installed.contains(feature)))
+}
+
+/// Candidates for `remove`
+pub fn installed_features() -> Vec<CompletionCandidate> {
+    candidates(installed().into_iter())
+}
+
+/// Candidates for `--installation`
+pub fn installation_names() -> Vec<CompletionCandidate> {
+    peek()
+        .map(|settings| settings.names())
+        .unwrap_or_default()
+        .into_iter()
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Candidates for `--preset`
+pub fn preset_names() -> Vec<CompletionCandidate> {
+    let built_in = FeatureSet::value_variants()
+        .iter()
+        .filter_map(|feature_set| feature_set.to_possible_value())
+        .map(|value| value.get_name().to_string());
+
+    // Reads the project's presets too, so the root has to be found first
+    let custom = settings_finder::enter_project_root(None)
+        .map(|()| settings_finder::custom_presets().keys().cloned().collect())
+        .unwrap_or_else(|_| Vec::new());
+
+    built_in
+        .chain(custom)
+        .map(CompletionCandidate::new)
+        .collect()
+}
+
+/// Features installed in the installation the command line is about
+fn installed() -> Vec<Feature> {
+    let Some(settings) = peek() else {
+        return Vec::new();
+    };
+
+    let installations: Vec<&ProjectChoices> = match requested_installation() {
+        Some(name) => settings.get(&name).into_iter().collect(),
+        None => match settings.installations.as_slice() {
+            [only] => vec![only],
+            // The command will ask for `--installation`, until then any of them may be meant
+            all => all.iter().collect(),
+        },
+    };
+
+    Feature::get_all()
+        .into_iter()
+        .filter(|feature| installations.iter().any(|choices| choices.features().contains(feature)))
+        .collect()
+}
+
+/// The `--installation` typed so far. Candidates are asked for without the
+/// other arguments, so they're read from the words the shell passed along
+fn requested_installation() -> Option<String> {
+    let words: Vec<String> = std::env::args().collect();
+    words.iter().enumerate().rev().find_map(|(index, word)| match word.strip_prefix("--installation") {
+        Some("") => words.get(index + 1).cloned(),
+        Some(value) => value.strip_prefix('=').map(str::to_string),
+        None => None,
+    })
+}
+
+fn peek() -> Option<Settings> {
+    // Only runs while completing, so nothing else depends on the directory
+    settings_finder::enter_project_root(None).ok()?;
+    settings_finder::peek_settings()
+}
+
+fn candidates(features: impl Iterator<Item = Feature>) -> Vec<CompletionCandidate> {
+    features
+        .map(|feature| CompletionCandidate::new(format!("{:?}", feature)))
+        .collect()
+}
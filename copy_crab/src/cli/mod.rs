@@ -0,0 +1,206 @@
+mod commands;
+mod completions;
+
+pub use commands::print_status;
+pub use completions::complete_if_requested;
+
+use std::path::PathBuf;
+
+use crate::error::Result;
+use crate::models::{Feature, PresetName, Runtime};
+use crate::settings_finder::ConfigHost;
+
+use clap::error::ErrorKind;
+use clap::{Args, CommandFactory, Parser, Subcommand};
+use clap_complete::ArgValueCandidates;
+
+/// An installer to install Crab Safe, but only the parts you want.
+///
+/// Run without a subcommand to be guided through the prompts instead.
+#[derive(Parser)]
+#[command(version, about)]
+pub struct Cli {
+    #[command(flatten)]
+    pub global: GlobalArgs,
+
+    #[command(subcommand)]
+    pub command: Option<Command>,
+}
+
+impl Cli {
+    /// Parses the arguments like `parse`. The prompts can't honour every
+    /// global flag, so those are refused without a subcommand
+    pub fn parse_checked() -> Self {
+        let cli = Self::parse();
+        if cli.command.is_none() {
+            if let Some(flag) = cli.global.subcommand_only_flag() {
+                Self::command()
+                    .error(ErrorKind::MissingSubcommand, format!("{flag} can only be used together with a subcommand"))
+                    .exit();
+            }
+        }
+
+        cli
+    }
+}
+
+#[derive(Args, Clone)]
+pub struct GlobalArgs {
+    /// Show a diff of what would change instead of writing anything
+    #[arg(long, global = true)]
+    pub dry_run: bool,
+
+    /// Print one JSON object per line instead of human-readable messages
+    #[arg(long, global = true)]
+    pub json: bool,
+
+    /// Use this directory as the project root instead of searching for
+    /// `copy-paste.json` in the current directory and its parents
+    #[arg(long, global = true, value_name = "DIR")]
+    pub project_root: Option<PathBuf>,
+
+    /// The installation to work on, when the project has several of them.
+    /// `init` creates an installation with this name
+    #[arg(long, global = true, value_name = "NAME", add = ArgValueCandidates::new(completions::installation_names))]
+    pub installation: Option<String>,
+}
+
+impl GlobalArgs {
+    /// The first flag that was passed but only works with a subcommand
+    fn subcommand_only_flag(&self) -> Option<&'static str> {
+        if self.dry_run {
+            Some("--dry-run")
+        } else if self.json {
+            Some("--json")
+        } else if self.installation.is_some() {
+            Some("--installation")
+        } else {
+            None
+        }
+    }
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Install crabSafe into the current project. Run it again with
+    /// `--installation` to add another installation, e.g. in a monorepo
+    Init(InitArgs),
+
+    /// Add features to an existing installation
+    Add {
+        #[arg(required = true, ignore_case = true, add = ArgValueCandidates::new(completions::uninstalled_features))]
+        features: Vec<Feature>,
+
+        /// Overwrite files that were edited by hand without asking
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Remove features from an existing installation
+    Remove {
+        #[arg(required = true, ignore_case = true, add = ArgValueCandidates::new(completions::installed_features))]
+        features: Vec<Feature>,
+
+        /// Overwrite files that were edited by hand without asking
+        #[arg(long)]
+        force: bool,
+    },
+
+    /// Explain why a feature is installed: chosen directly, or needed by
+    /// other installed features
+    Why {
+        #[arg(ignore_case = true, add = ArgValueCandidates::new(completions::installed_features))]
+        feature: Feature,
+    },
+
+    /// Show the installed features and whether their files were changed.
+    /// Covers every installation unless `--installation` is given
+    Status,
+
+    /// Fail if the installed files differ from what the configuration describes.
+    /// Covers every installation unless `--installation` is given
+    Check,
+
+    /// Regenerate the files described by the stored configuration.
+    /// Covers every installation unless `--installation` is given
+    Sync,
+
+    /// Remove the entire crabSafe installation from the project
+    Uninstall {
+        /// Don't ask for confirmation
+        #[arg(short, long)]
+        yes: bool,
+    },
+
+    /// Move the crabSafe settings into another file. They are read from the
+    /// first of copy-paste.json, copy-paste.toml, deno.json and package.json
+    /// that has them
+    MoveConfig {
+        to: ConfigHost,
+    },
+
+    /// Print a completion script. Load it with e.g. `source <(copy_crab completions bash)`
+    Completions {
+        shell: completions::Shell,
+    },
+
+    /// Print the man page
+    Man,
+}
+
+impl Command {
+    /// Commands whose output is meant to be sourced or saved as-is
+    pub fn prints_raw(&self) -> bool {
+        matches!(self, Command::Completions { .. } | Command::Man)
+    }
+}
+
+/// Options left out are taken from the user-level config
+/// (`~/.config/copy_crab/config.json`)
+#[derive(Args)]
+pub struct InitArgs {
+    /// The kind of project crabSafe is brought into
+    #[arg(long)]
+    pub runtime: Option<Runtime>,
+
+    /// Directory the crabSafe implementation is written into, relative to
+    /// the current directory
+    #[arg(long)]
+    pub dir: Option<String>,
+
+    /// Install a predefined set of features: all, core, core-plus or the
+    /// name of a preset defined in `copy-paste.json` or the user-level config
+    #[arg(long, conflicts_with = "features", add = ArgValueCandidates::new(completions::preset_names))]
+    pub preset: Option<PresetName>,
+
+    /// Install exactly these features
+    #[arg(long, num_args = 1.., ignore_case = true)]
+    pub features: Vec<Feature>,
+
+    /// Put every feature in its own file inside a `crabSafe` directory
+    #[arg(long)]
+    pub split: bool,
+
+    /// Put every feature into a single `crabSafe.ts`, even if the user-level
+    /// config says otherwise
+    #[arg(long, conflicts_with = "split")]
+    pub single_file: bool,
+}
+
+pub fn run(command: Command, global: GlobalArgs) -> Result<()> {
+    let dry_run = global.dry_run;
+    let installation = global.installation.as_deref();
+    match command {
+        Command::Init(args) => commands::init(args, installation, dry_run),
+        Command::Add { features, force } => commands::add(features, installation, dry_run, force),
+        Command::Remove { features, force } => commands::remove(features, installation, dry_run, force),
+        Command::Why { feature } => commands::why(feature, installation),
+        Command::Status => commands::status(installation),
+        Command::Check => commands::check(installation),
+        Command::Sync => commands::sync(installation, dry_run),
+        Command::Uninstall { yes } => commands::uninstall(yes, installation, dry_run),
+        Command::MoveConfig { to } => commands::move_config(to, dry_run),
+        Command::Completions { shell } => completions::print_completions(shell),
+        Command::Man => completions::print_man_page(),
+    }
+}
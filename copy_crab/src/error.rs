@@ -0,0 +1,93 @@
+use std::{io, path::Path};
+
+use inquire::InquireError;
+use thiserror::Error;
+
+pub type Result<T, E = CopyCrabError> = std::result::Result<T, E>;
+
+#[derive(Debug, Error)]
+pub enum CopyCrabError {
+    #[error("{path} is not valid JSON: {source}")]
+    ConfigParse { path: String, source: serde_json::Error },
+
+    #[error(
+        "A key of {key} was found in {path}, but it contained invalid configuration settings. \
+        Please delete the key-value pair if you want to import this library from this tool"
+    )]
+    InvalidSettings { key: String, path: String, source: serde_json::Error },
+
+    #[error(
+        "{path} was written by a newer copy_crab (schema v{found}, this version only knows up to v{}). \
+        Please update copy_crab",
+        crate::settings_finder::SCHEMA_VERSION
+    )]
+    UnsupportedSchema { path: String, found: u64 },
+
+    #[error("Couldn't access {path}: {source}")]
+    Io { path: String, source: io::Error },
+
+    #[error("Cancelled, nothing was written")]
+    Cancelled,
+
+    #[error("Couldn't show the prompt: {0}. Use the subcommands to run without a terminal")]
+    Prompt(InquireError),
+
+    /// The chosen options can't be used together (or are missing)
+    #[error("{0}")]
+    InvalidCombination(String),
+
+    /// The project is not in a state the operation can be applied to
+    #[error("{0}")]
+    Conflict(String),
+
+    #[error("crabSafe is not installed in this project. Run `copy_crab init` first")]
+    NotInstalled,
+
+    #[error("{0} file(s) drifted from the configuration. Run `copy_crab sync` to fix them")]
+    Drift(usize),
+
+    #[error("Another copy_crab is already working on this project. Try again once it's done (lock: {path})")]
+    Locked { path: String },
+}
+
+impl CopyCrabError {
+    /// Stable exit codes, so scripts can tell failures apart.
+    /// 2 is left to clap, which uses it for invalid arguments
+    pub fn exit_code(&self) -> i32 {
+        use CopyCrabError as E;
+        match self {
+            E::Drift(_) => 1,
+            E::ConfigParse { .. } | E::InvalidSettings { .. } | E::UnsupportedSchema { .. } => 3,
+            E::Io { .. } => 4,
+            E::InvalidCombination(_) => 5,
+            E::Conflict(_) => 6,
+            E::NotInstalled => 7,
+            E::Prompt(_) => 8,
+            E::Locked { .. } => 9,
+            E::Cancelled => 130,
+        }
+    }
+}
+
+impl From<InquireError> for CopyCrabError {
+    fn from(err: InquireError) -> Self {
+        match err {
+            InquireError::OperationCanceled | InquireError::OperationInterrupted => CopyCrabError::Cancelled,
+            err => CopyCrabError::Prompt(err),
+        }
+    }
+}
+
+/// Attaches the path an I/O operation failed on
+pub trait IoContext<T> {
+    fn at(self, path: impl AsRef<Path>) -> Result<T>;
+}
+
+impl<T> IoContext<T> for io::Result<T> {
+    fn at(self, path: impl AsRef<Path>) -> Result<T> {
+        self.map_err(|source| CopyCrabError::Io {
+            path: path.as_ref().display().to_string(),
+            source,
+        })
+    }
+}
@@ -1,71 +1,143 @@
-use crate::models::{ProjectChoices, Runtime, ProjectBuilder, FeatureSet, Modularity, ChosenFeatures, Feature};
+use crate::models::{ProjectChoices, Runtime, ProjectBuilder, FeatureSet, PresetName, Modularity, ChosenFeatures, Feature};
+use crate::settings_finder::{self, UserDefaults};
+
+use crate::error::{CopyCrabError, Result};
+use super::OrBack;
 
 use inquire::{ Select, Text, MultiSelect };
 
-pub fn inquire_main() -> ProjectChoices {
-    ProjectBuilder::new()
-        .set_runtime( ask_runtime() )
-        .set_chosen_dir( ask_chosen_dir() )
-        .set_feature_set( ask_feature_from() )
-        .set_modularity( ask_modularity() )
-        .build()
+/// Asks every question in order. Esc goes back to the previous question
+/// (or quits on the first one), so nothing is written until all are answered
+pub fn inquire_main() -> Result<ProjectChoices> {
+    inquire_steps()?.ok_or(CopyCrabError::Cancelled)
+}
+
+/// Like `inquire_main`, but pressing Esc on the first question returns `None`
+/// so the caller can go back to its own menu. The answers from the user-level
+/// config are pre-selected
+pub fn inquire_steps() -> Result<Option<ProjectChoices>> {
+    let defaults = settings_finder::user_defaults()?;
+    let mut builder = ProjectBuilder::new();
+    let mut step = 0;
+
+    while step < 4 {
+        let answered = match step {
+            0 => ask_runtime(&defaults)?.map(|ans| builder.clone().set_runtime(ans)),
+            1 => ask_chosen_dir(&defaults)?.map(|ans| builder.clone().set_chosen_dir(ans)),
+            2 => ask_feature_from(&defaults)?.map(|ans| builder.clone().set_feature_set(ans)),
+            _ => ask_modularity(&defaults)?.map(|ans| builder.clone().set_modularity(ans)),
+        };
+
+        match answered {
+            Some(next_builder) => {
+                builder = next_builder;
+                step += 1;
+            },
+            None if step == 0 => return Ok(None),
+            None => step -= 1,
+        }
+    }
+
+    builder.build().map(Some)
 }
 
-fn ask_runtime() -> Runtime {
+fn ask_runtime(defaults: &UserDefaults) -> Result<Option<Runtime>> {
     let options: Vec<&str> = vec![
         "Deno",
         "NodeJS",
         "client-side (React, Svelte, Vue, etc)",
     ];
 
-    let message = "What project are you bringing crabSafe into?";
-    let ans = Select::new(message, options).prompt();
     use Runtime as R;
+    let cursor = match defaults.runtime {
+        Some(R::Deno) | None => 0,
+        Some(R::NodeJs) => 1,
+        Some(R::ClientSide) => 2,
+    };
+
+    let message = "What project are you bringing crabSafe into?";
+    let Some(ans) = Select::new(message, options).with_starting_cursor(cursor).prompt().or_back()? else {
+        return Ok(None);
+    };
     match ans {
-        Ok("Deno") => R::Deno,
-        Ok("NodeJS") => R::NodeJs,
-        Ok("client-side (React, Svelte, Vue, etc)") => R::ClientSide,
-        _ => panic!("An invalid option was chosen!"),
+        "Deno" => Ok(Some(R::Deno)),
+        "NodeJS" => Ok(Some(R::NodeJs)),
+        "client-side (React, Svelte, Vue, etc)" => Ok(Some(R::ClientSide)),
+        other => Err(invalid_option(other)),
     }
 }
 
-fn ask_chosen_dir() -> String {
-    let options: Vec<&str> = vec![
-        "Type in path to directory",
-        "Browse...",
-    ];
+fn ask_chosen_dir(defaults: &UserDefaults) -> Result<Option<String>> {
+    // Only offered if it exists in this project
+    let default_dir = defaults.chosen_directory
+        .as_ref()
+        .filter(|dir| std::fs::metadata(dir).is_ok_and(|meta| meta.is_dir()))
+        .map(|dir| format!("Use {dir}"));
+
+    // Pressing Esc while typing goes back to choosing the method
+    loop {
+        let mut options: Vec<&str> = vec![
+            "Type in path to directory",
+            "Browse...",
+        ];
+        if let Some(default_dir) = &default_dir {
+            options.insert(0, default_dir);
+        }
+
+        let message = "Choose a method to select directory";
+        let Some(ans) = Select::new(message, options).prompt().or_back()? else {
+            return Ok(None);
+        };
+
+        let found_dir = match ans {
+            "Type in path to directory" => ask_typed_dir()?,
+            "Browse..." => ask_browse_dir()?,
+            _ => defaults.chosen_directory.clone(),
+        };
+
+        if found_dir.is_some() {
+            return Ok(found_dir);
+        }
+    }
+}
+
+fn ask_typed_dir() -> Result<Option<String>> {
+    // Ask them to type the path into the directory
+    let Some(typed_dir) = Text::new("Enter path:").prompt().or_back()? else {
+        return Ok(None);
+    };
+    let mut found_dir = crate::settings_finder::resolve_path(&typed_dir);
 
-    let message = "Choose a method to select directory";
-    let ans = Select::new(message, options).prompt();
-    if let Ok("Type in path to directory") = ans {
-        // Ask them to type the path into the directory
-        let mut found_dir = Text::new("Enter path:")
+    while std::fs::metadata(&found_dir).is_err() {
+        let retry = Text::new("Invalid directory. Press ENTER to type in a folder path")
             .prompt()
-            .expect("Path not entered. Quitting");
-
-        
-        while let Err(_) = std::fs::metadata(&found_dir) {
-            Text::new("Invalid directory. Press ENTER to type in a folder path")
-                .prompt()
-                .unwrap();
-            found_dir = Text::new("Enter path:")
-                .prompt()
-                .expect("Path not entered. Quitting");
+            .or_back()?;
+        if retry.is_none() {
+            return Ok(None);
         }
 
-        return found_dir;
+        let Some(retyped_dir) = Text::new("Enter path:").prompt().or_back()? else {
+            return Ok(None);
+        };
+        found_dir = crate::settings_finder::resolve_path(&retyped_dir);
     }
-    
-    // Otherwise open the file browser
+
+    Ok(Some(found_dir))
+}
+
+fn ask_browse_dir() -> Result<Option<String>> {
     let mut directory_choice = rfd::FileDialog::new()
         .set_can_create_directories(true)
         .set_title("Choose a directory...")
         .pick_folder();
 
-    while let None = directory_choice {
-        Text::new("Directory not selected. Press ENTER to pick a folder")
+    while directory_choice.is_none() {
+        let retry = Text::new("Directory not selected. Press ENTER to pick a folder")
             .prompt()
-            .unwrap();
+            .or_back()?;
+        if retry.is_none() {
+            return Ok(None);
+        }
 
         directory_choice = rfd::FileDialog::new()
             .set_can_create_directories(true)
@@ -73,86 +145,109 @@ fn ask_chosen_dir() -> String {
             .pick_folder();
     }
 
+    let directory_choice = directory_choice.unwrap_or_default();
     directory_choice
-      .expect("Couldn't unwrap selected folder")
       .to_str()
-      .expect("Couldn't convert pathbuf to string")
-      .to_string()
+      .map(|dir| Some(dir.to_string()))
+      .ok_or_else(|| CopyCrabError::InvalidCombination(
+          format!("{} is not a valid UTF-8 path", directory_choice.display())
+      ))
 }
 
-fn ask_feature_from() -> ChosenFeatures {
+fn ask_feature_from(defaults: &UserDefaults) -> Result<Option<ChosenFeatures>> {
     let options: Vec<&str> = vec![
         "From Preset",
         "Custom",
     ];
 
     let message = "How would you like to choose features?";
-    let ans = Select::new(message, options).prompt();
 
-    use ChosenFeatures as CF;
-    match ans {
-        Ok("From Preset") => CF::Preset { preset_name: ask_feature_preset() },
-        Ok("Custom") => CF::Custom { features: ask_feature_multichoice() },
-        _ => panic!("An invalid option was chosen!"),
+    // Pressing Esc on the follow-up question comes back here
+    loop {
+        let Some(ans) = Select::new(message, options.clone()).prompt().or_back()? else {
+            return Ok(None);
+        };
+
+        use ChosenFeatures as CF;
+        let chosen = match ans {
+            "From Preset" => ask_feature_preset(defaults)?.map(|preset_name| CF::Preset { preset_name }),
+            "Custom" => ask_feature_multichoice()?.map(|features| CF::Custom { features }),
+            other => return Err(invalid_option(other)),
+        };
+
+        if chosen.is_some() {
+            return Ok(chosen);
+        }
     }
 }
 
-fn ask_feature_preset() -> FeatureSet {
-    let options: Vec<&str> = vec![
-        "All",
-        "Core",
-        "Core + Option and Result",
+fn ask_feature_preset(defaults: &UserDefaults) -> Result<Option<PresetName>> {
+    use FeatureSet as F;
+    let mut presets: Vec<(String, PresetName)> = vec![
+        ("All".to_string(), PresetName::BuiltIn(F::All)),
+        ("Core".to_string(), PresetName::BuiltIn(F::Core)),
+        ("Core + Option and Result".to_string(), PresetName::BuiltIn(F::CorePlus)),
     ];
 
-    let message = "Which crab-safe features do you want?";
-    let ans = Select::new(message, options).prompt();
+    // Presets from copy-paste.json and the user-level config. Built-in names win
+    for (name, features) in settings_finder::custom_presets() {
+        if F::from_name(name).is_some() {
+            continue;
+        }
 
-    use FeatureSet as F;
-    match ans {
-        Ok("All") => F::All,
-        Ok("Core") => F::Core,
-        Ok("Core + Option and Result") => F::CorePlus,
-        _ => panic!("An invalid option was chosen!"),
+        let feature_names: Vec<String> = features.iter().map(Feature::display_name).collect();
+        presets.push((format!("{name} ({})", feature_names.join(", ")), PresetName::Custom(name.clone())));
     }
+
+    let cursor = presets
+        .iter()
+        .position(|(_, preset_name)| defaults.preset.as_ref() == Some(preset_name))
+        .unwrap_or(0);
+    let options: Vec<&str> = presets.iter().map(|(label, _)| label.as_str()).collect();
+
+    let message = "Which crab-safe features do you want?";
+    let Some(ans) = Select::new(message, options).with_starting_cursor(cursor).raw_prompt().or_back()? else {
+        return Ok(None);
+    };
+
+    Ok(Some(presets.swap_remove(ans.index).1))
 }
 
-fn ask_feature_multichoice() -> Vec<Feature> {
-    let options = vec![
-        "Core",
-        "Example",
-        "Option",
-        "Result",
-        "Parsers",
-    ];
+fn ask_feature_multichoice() -> Result<Option<Vec<Feature>>> {
+    let features = Feature::get_all();
+    let options: Vec<String> = features.iter().map(Feature::display_name).collect();
+
+    let ans = MultiSelect::new("Select which feature you want", options).raw_prompt().or_back()?;
+    let Some(ans) = ans else {
+        return Ok(None);
+    };
 
-    let ans = MultiSelect::new("Select which feature you want", options).prompt()
-        .expect("Features not chosen!");
-
-    use Feature as F;
-    ans.iter()
-       .map(|s| match *s {
-            "Core" => F::Core,
-            "Example" => F::Example,
-            "Option" => F::Option,
-            "Result" => F::Result,
-            "Parsers" => F::Parsers,
-            _ => panic!("Match statement didn't work")
-       })
-       .collect()
+    Ok(Some(ans.iter().map(|option| features[option.index].clone()).collect()))
 }
 
-fn ask_modularity() -> Modularity {
+fn ask_modularity(defaults: &UserDefaults) -> Result<Option<Modularity>> {
     let options: Vec<&str> = vec![
         "Same file",
         "Separate files",
     ];
 
-    let message = "Do you want the crabsafe implementations to be in separate files or in the same file?";
-    let ans = Select::new(message, options).prompt();
     use Modularity as M;
+    let cursor = match defaults.modularity {
+        Some(M::SingleFile) | None => 0,
+        Some(M::SplitFiles) => 1,
+    };
+
+    let message = "Do you want the crabsafe implementations to be in separate files or in the same file?";
+    let Some(ans) = Select::new(message, options).with_starting_cursor(cursor).prompt().or_back()? else {
+        return Ok(None);
+    };
     match ans {
-        Ok("Same file") => M::SingleFile,
-        Ok("Separate files") => M::SplitFiles,
-        _ => panic!("An invalid option was chosen!"),
+        "Same file" => Ok(Some(M::SingleFile)),
+        "Separate files" => Ok(Some(M::SplitFiles)),
+        other => Err(invalid_option(other)),
     }
+}
+
+fn invalid_option(option: &str) -> CopyCrabError {
+    CopyCrabError::InvalidCombination(format!("{option} is not a valid option"))
 }
\ No newline at end of file
@@ -1,2 +1,22 @@
 pub mod first_time;
-pub mod other_times;
\ No newline at end of file
+pub mod other_times;
+
+use crate::error::Result;
+
+use inquire::{InquireError, error::InquireResult};
+
+/// Esc takes the user one step back, while Ctrl-C still quits
+pub trait OrBack<T> {
+    /// `None` means the user pressed Esc
+    fn or_back(self) -> Result<Option<T>>;
+}
+
+impl<T> OrBack<T> for InquireResult<T> {
+    fn or_back(self) -> Result<Option<T>> {
+        match self {
+            Ok(ans) => Ok(Some(ans)),
+            Err(InquireError::OperationCanceled) => Ok(None),
+            Err(err) => Err(err.into()),
+        }
+    }
+}
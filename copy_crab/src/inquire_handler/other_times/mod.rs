@@ -1,50 +1,133 @@
-#![allow(unused)]
-use std::{cell::RefCell, collections::HashSet, str::FromStr};
-use crate::models::{ProjectChoices, Runtime, ProjectBuilder, FeatureSet, Modularity, ChosenFeatures, Feature};
+use crate::models::{ProjectChoices, ChosenFeatures, Feature, HandEdits, Settings};
 
-use anyhow::Result;
-use inquire::{ Select, Text, MultiSelect, Confirm };
+use crate::error::{CopyCrabError, Result};
+use super::{first_time, OrBack};
+use inquire::{ Select, MultiSelect, Confirm, Text };
 use colored::Colorize;
 
-// Treat it like a preact-signal to avoid "prop drilling"
-static mut CHOICES: RefCell<Option<ProjectChoices>> = RefCell::new(None);
-
-pub fn inquire_main(choices: ProjectChoices) -> Result<()> {
-    println!(
-        "Found previous configuration settings for {} project",
-        format!("{:?}", &choices.runtime).bold().bright_cyan()
-    );
+/// Where the main menu goes after a handler is done
+enum Next {
+    Menu,
+    Exit,
+    /// Another installation should be added to the project
+    NewInstallation,
+    /// The installation being modified was removed
+    Deleted,
+}
 
-    modify_choices(choices);
+pub fn inquire_main(mut settings: Settings) -> Result<()> {
+    let Some(mut choices) = ask_installation(&settings)? else {
+        return Ok(());
+    };
 
-    ask_next_steps()?;
-    Ok(())
+    // Every change is applied right away, so `choices` always matches
+    // what is saved in the config file
+    loop {
+        match ask_next_steps(&mut choices)? {
+            Next::Menu => {},
+            Next::Exit => return Ok(()),
+            Next::NewInstallation => {
+                settings.upsert(&choices);
+                if let Some(new_choices) = handle_new_installation(&settings)? {
+                    settings.upsert(&new_choices);
+                    choices = new_choices;
+                }
+            },
+            Next::Deleted => {
+                settings.remove(&choices.name);
+                if settings.installations.is_empty() {
+                    // Nothing is left to manage
+                    return Ok(());
+                }
+
+                let Some(next_choices) = ask_installation(&settings)? else {
+                    return Ok(());
+                };
+                choices = next_choices;
+            },
+        }
+    }
 }
 
-fn modify_choices(choices: ProjectChoices) {
-    let mut choices_cell = unsafe { CHOICES.borrow_mut() };
-    *choices_cell = Some(choices);
+/// Lets the user pick which installation to modify. Only asks when there is
+/// more than one
+fn ask_installation(settings: &Settings) -> Result<Option<ProjectChoices>> {
+    let choices = match settings.installations.as_slice() {
+        [only] => only.clone(),
+        installations => {
+            let options: Vec<String> = installations
+                .iter()
+                .map(|choices| format!("{} ({:?}, {})", choices.name, choices.runtime, choices.chosen_directory))
+                .collect();
+
+            let message = "Which installation do you want to modify?";
+            let Some(ans) = Select::new(message, options).raw_prompt().or_back()? else {
+                return Ok(None);
+            };
+            installations[ans.index].clone()
+        }
+    };
+
+    println!(
+        "Found previous configuration settings for {} project {}",
+        format!("{:?}", &choices.runtime).bold().bright_cyan(),
+        format!("({})", choices.name).dimmed()
+    );
+
+    Ok(Some(choices))
 }
 
-fn ask_next_steps() -> Result<()> {
+fn ask_next_steps(choices: &mut ProjectChoices) -> Result<Next> {
     let options: Vec<&str> = vec![
+        "ℹ View current config",
         "❄ Modify Package",
+        "⟳ Sync files",
+        "✚ New installation",
         "✖ Delete crabSafe",
+        "⏻ Exit",
     ];
 
     let message = "What would you like to do?";
-    let ans = Select::new(message, options).prompt();
-    use Runtime as R;
+    let Some(ans) = Select::new(message, options).prompt().or_back()? else {
+        return Ok(Next::Exit);
+    };
     match ans {
-        Ok("❄ Modify Package") => handle_modify(),
-        Ok("✖ Delete crabSafe") => handle_delete_crabsafe(),
-        _ => panic!("An invalid option was chosen!"),
+        "ℹ View current config" => handle_status(choices),
+        "❄ Modify Package" => handle_modify(choices),
+        "⟳ Sync files" => handle_sync(choices),
+        "✚ New installation" => Ok(Next::NewInstallation),
+        "✖ Delete crabSafe" => handle_delete_crabsafe(choices),
+        "⏻ Exit" => Ok(Next::Exit),
+        other => Err(invalid_option(other)),
+    }
+}
+
+/// Asks for a name and the usual first-time questions, then writes the new
+/// installation next to the existing ones
+fn handle_new_installation(settings: &Settings) -> Result<Option<ProjectChoices>> {
+    let name = Text::new("Name of the new installation:")
+        .with_help_message("e.g. backend or frontend")
+        .prompt()
+        .or_back()?;
+    let Some(name) = name.map(|name| name.trim().to_string()).filter(|name| !name.is_empty()) else {
+        return Ok(None);
     };
 
-    Ok(())
+    let Some(mut new_choices) = first_time::inquire_steps()? else {
+        return Ok(None);
+    };
+    new_choices.name = name;
+
+    if let Err(err) = settings.check_new(&new_choices) {
+        println!("{} {err}", "Warning:".black().on_yellow());
+        return Ok(None);
+    }
+
+    new_choices.handle(HandEdits::Keep)?;
+    Ok(Some(new_choices))
 }
 
-fn handle_modify() {
+fn handle_modify(choices: &mut ProjectChoices) -> Result<Next> {
     let options: Vec<&str> = vec![
         "✚ Add package",
         "✖ Remove package",
@@ -52,18 +135,34 @@ fn handle_modify() {
     ];
 
     let message = "Choose aspect to modify";
-    let ans = Select::new(message, options).prompt();
-    use Runtime as R;
-    match ans {
-        Ok("✚ Add package") => handle_add(),
-        Ok("✖ Remove package") => handle_delete_package(),
-        Ok("⮜ Go Back") => ask_next_steps().unwrap(),
-        _ => panic!("An invalid option was chosen!"),
+    let Some(ans) = Select::new(message, options).prompt().or_back()? else {
+        return Ok(Next::Menu);
     };
+    match ans {
+        "✚ Add package" => handle_add(choices),
+        "✖ Remove package" => handle_delete_package(choices),
+        "⮜ Go Back" => Ok(Next::Menu),
+        other => Err(invalid_option(other)),
+    }
+}
+
+fn handle_status(choices: &ProjectChoices) -> Result<Next> {
+    crate::cli::print_status(choices)?;
+    Ok(Next::Menu)
+}
+
+/// Regenerates the files described by the stored choices
+fn handle_sync(choices: &ProjectChoices) -> Result<Next> {
+    let drift = choices.sync()?;
+    for file in &drift {
+        println!("{file}");
+    }
+
+    Ok(Next::Menu)
 }
 
 /// Removes the entire crabsafe, not individual modules
-fn handle_delete_crabsafe() {
+fn handle_delete_crabsafe(choices: &ProjectChoices) -> Result<Next> {
     let message = format!(
         "{} {}\n  {} {}",
         "WARN:".black().on_red(),
@@ -74,53 +173,34 @@ fn handle_delete_crabsafe() {
     let ans = Confirm::new(&message)
         .with_default(false)
         .with_help_message("Make sure to remove all local implementations that depend on these methods!")
-        .prompt();
+        .prompt()
+        .or_back()?;
 
-    if let Ok(true) = ans {
-        let mut choices_signal = unsafe { CHOICES.borrow() };
-        let project_choices = choices_signal
-            .as_ref()
-            .unwrap();
-
-        crate::settings_finder::remove_completely(project_choices).unwrap();
+    if let Some(true) = ans {
+        crate::settings_finder::remove_completely(choices)?;
+        return Ok(Next::Deleted);
     }
+
+    Ok(Next::Menu)
 }
 
 // REFACTOR: with the handle_add function
-fn handle_delete_package() {
-    // Turn this into a list of unadded modules
-    let mut choices_signal = unsafe { CHOICES.borrow_mut() };
-    let mut project_choices: &mut ProjectChoices = choices_signal
-        .as_mut()
-        .unwrap();
-
-    let init_features = project_choices
-        .feature_set
-        .get_feature_list();
-
-    let binding: Vec<String> = init_features
-        .iter()
-        .map(|feature| format!("{:?}", feature))
-        .collect();
+fn handle_delete_package(project_choices: &mut ProjectChoices) -> Result<Next> {
+    let init_features = project_choices.features();
 
-    let options: Vec<&str> = binding.iter().map(|s| s.as_str()).collect();
+    let options: Vec<String> = init_features.iter().map(Feature::display_name).collect();
 
-    let message = "What do you want to remove?";
-    let ans = MultiSelect::new("Select packages", options).prompt()
-        .expect("packages not chosen!");
-
-    use Feature as F;
-    let selected_features: HashSet<Feature> = ans.iter()
-        .map(|s| Feature::from_str(s).expect("Chosen Features couldn't be parsed"))
-        .collect();
+    let Some(ans) = MultiSelect::new("Select packages", options).raw_prompt().or_back()? else {
+        return Ok(Next::Menu);
+    };
 
-    let features: Vec<Feature> = init_features
-        .iter()
-        .filter(|f| !selected_features.contains(&f))
-        .cloned()
+    let selected_features: Vec<Feature> = ans.iter()
+        .map(|option| init_features[option.index].clone())
         .collect();
 
-    if features.len() == 0 {
+    // What the chosen features need goes away together with them
+    let chosen = project_choices.feature_set.get_feature_list();
+    if chosen.iter().all(|feature| selected_features.contains(feature)) {
         // It doesn't make sense to have an packageless version of this
         // Ask them if they want to delete the whole project instead
         let message = format!(
@@ -129,17 +209,25 @@ fn handle_delete_package() {
         );
         let ans = Confirm::new(&message)
             .with_default(true)
-            .prompt();
-
-        if let Ok(true) = ans {
-            // Remove ownership of shared value
-            std::mem::drop(choices_signal);
-            handle_delete_crabsafe();
+            .prompt()
+            .or_back()?;
 
-            return;
+        if let Some(true) = ans {
+            return handle_delete_crabsafe(project_choices);
         }
+        return Ok(Next::Menu);
     }
 
+    let updated_choices = match project_choices.without(&selected_features) {
+        Ok(updated_choices) => updated_choices,
+        Err(CopyCrabError::Conflict(message)) => {
+            println!("{}", message.yellow());
+            return Ok(Next::Menu);
+        },
+        Err(err) => return Err(err),
+    };
+    preview_changes(&updated_choices)?;
+
     let message = format!(
         "{} {}\n  {} {}",
         "Warning:".black().on_yellow(),
@@ -150,53 +238,47 @@ fn handle_delete_package() {
 
     let ans = Confirm::new(&message)
         .with_default(false)
-        .prompt();
+        .prompt()
+        .or_back()?;
 
-    if let Ok(true) = ans {
+    if let Some(true) = ans {
         println!("{}", "Changing data".bright_green());
-        project_choices.feature_set = ChosenFeatures::Custom { features };
-
-        if let Modularity::SplitFiles = project_choices.modularity {
-            // Delete all the contents of the file
-            recreate(&project_choices.chosen_directory)
-                .expect("Couldn't recreate directory");
-        }
-
-        // We no longer need to update choices signal as we will be
-        // passing project_choices one last time
-        project_choices.handle();
+        // Files of removed features are cleaned up by `handle`
+        updated_choices.handle(HandEdits::OverwriteChanged)?;
+        *project_choices = updated_choices;
     }
+
+    Ok(Next::Menu)
 }
 
-fn handle_add() {
+fn handle_add(project_choices: &mut ProjectChoices) -> Result<Next> {
     // Turn this into a list of unadded modules
-    let mut choices_signal = unsafe { CHOICES.borrow_mut() };
-    let mut project_choices: &mut ProjectChoices = choices_signal
-        .as_mut()
-        .unwrap();
-
     let init_features = project_choices
         .feature_set
         .get_feature_list();
 
-    let binding: Vec<String> = Feature::get_complements(&init_features)
-        .iter()
-        .map(|feature| format!("{:?}", feature))
-        .collect();
+    let complements = Feature::get_complements(&project_choices.features());
+    if complements.is_empty() {
+        println!("{}", "Every package is already installed".truecolor(0, 220, 150));
+        return Ok(Next::Menu);
+    }
 
-    let options: Vec<&str> = binding.iter().map(|s| s.as_str()).collect();
+    let options: Vec<String> = complements.iter().map(Feature::display_name).collect();
 
-    let message = "Choose a package to add";
-    let ans = MultiSelect::new("Select which feature you want", options).prompt()
-        .expect("Features not chosen!");
+    let Some(ans) = MultiSelect::new("Select which feature you want", options).raw_prompt().or_back()? else {
+        return Ok(Next::Menu);
+    };
 
-    use Feature as F;
     let fin_features: Vec<Feature> = ans.iter()
-        .map(|s| Feature::from_str(s).expect("Chosen Features couldn't be parsed"))
+        .map(|option| complements[option.index].clone())
         .collect();
 
     let features = [init_features, fin_features].concat();
 
+    let mut updated_choices = project_choices.clone();
+    updated_choices.feature_set = ChosenFeatures::Custom { features };
+    preview_changes(&updated_choices)?;
+
     let message = format!(
         "{} {}\n  {} {}",
         "WARN:".black().on_yellow(),
@@ -206,49 +288,32 @@ fn handle_add() {
     );
     let ans = Confirm::new(&message)
         .with_default(false)
-        .prompt();
+        .prompt()
+        .or_back()?;
 
-    if let Ok(true) = ans {
+    if let Some(true) = ans {
         println!("{}", "Changing data".bright_green());
-        project_choices.feature_set = ChosenFeatures::Custom { features };
-
-        // We no longer need to update choices signal as we will be
-        // passing project_choices one last time
-        project_choices.handle();
+        updated_choices.handle(HandEdits::OverwriteChanged)?;
+        *project_choices = updated_choices;
     }
+
+    Ok(Next::Menu)
 }
 
-// Misc functions: I couldn't put it in a closure due to lack of feature support :(
-fn recreate(s: &str) -> anyhow::Result<()> {
-    std::fs::remove_dir_all(&s)?;
-    std::fs::create_dir_all(&s)?;
+/// Offers to show the diff of what applying `updated_choices` would change
+fn preview_changes(updated_choices: &ProjectChoices) -> Result<()> {
+    let ans = Confirm::new("Preview changes first?")
+        .with_default(false)
+        .prompt()
+        .or_back()?;
+
+    if let Some(true) = ans {
+        println!("{}", crate::models::render_plan(&updated_choices.plan(HandEdits::OverwriteChanged)?));
+    }
+
     Ok(())
 }
 
-enum Pet {
-    Cat {
-        num_whiskers: u8,
-        claws: bool,
-    },
-    Dog {
-        bark_volume: u8,
-        tail_wagging_speed: u8,
-    },
+fn invalid_option(option: &str) -> CopyCrabError {
+    CopyCrabError::InvalidCombination(format!("{option} is not a valid option"))
 }
-
-fn approach_pet(pet: Pet) {
-    match pet {
-        Pet::Cat { num_whiskers, claws } => if claws {
-                "Be careful".to_string()
-            } else {
-                format!("Approach cat with {numWhiskers} whiskers")
-            },
-        Pet::Dog { bark_volume, tail_wagging_speed } => {
-            if tail_wagging_speed > 50 {
-                "It wants you".to_string()
-            } else {
-                "Go earn its love".to_string()
-            }
-        }
-    }
-}
\ No newline at end of file
@@ -1,28 +1,61 @@
 mod ts_file_data;
+mod error;
 mod models;
 mod inquire_handler;
 mod settings_finder;
+mod cli;
+mod output;
 
+use colored::Colorize;
+use error::CopyCrabError;
 use inquire_handler::{first_time, other_times};
 
 fn main() {
-    let config = settings_finder::find_settings().unwrap();
+    cli::complete_if_requested();
+
+    let args = cli::Cli::parse_checked();
+    let prints_raw = args.command.as_ref().is_some_and(cli::Command::prints_raw);
+    output::set_json(args.global.json);
+
+    // Completions and the man page don't touch the project, so they don't wait for it
+    let res = settings_finder::enter_project_root(args.global.project_root.as_deref())
+        .and_then(|()| (!prints_raw).then(settings_finder::lock_project).transpose())
+        .and_then(|_lock| match args.command {
+            Some(command) => cli::run(command, args.global),
+            None => run_interactive(),
+        });
+
+    // Scripts rely on the exit code, so don't panic on failure
+    if let Err(err) = res {
+        match err {
+            // Not a failure, the user just wanted out
+            CopyCrabError::Cancelled => output::info(err.to_string().dimmed()),
+            _ => output::emit(output::Event::Error { message: err.to_string() }),
+        }
+        std::process::exit(err.exit_code());
+    }
+
+    if !prints_raw {
+        output::info("Done!");
+    }
+}
+
+fn run_interactive() -> error::Result<()> {
+    let config = settings_finder::find_settings()?;
 
     match config {
-        // TODO: Complete this part
-        Some(found_config) => 
-            other_times::inquire_main(found_config).unwrap(),
+        Some(found_settings) =>
+            other_times::inquire_main(found_settings),
 
         None => {
             // Ask the user
-            let project_choices = first_time::inquire_main();
+            let project_choices = first_time::inquire_main()?;
 
             // Transform the data
-            project_choices.handle().unwrap();
+            project_choices.handle(models::HandEdits::Keep)?;
+            Ok(())
         }
     }
-
-    println!("Done!")
 }
 
 fn parse_path(chosen_directory: &str) -> (&str, &str) {
@@ -31,12 +64,12 @@ fn parse_path(chosen_directory: &str) -> (&str, &str) {
         None => match chosen_directory.strip_suffix("\\") {
             Some(rem_path) => (rem_path, "\\"),
 
-            None => if chosen_directory.contains("/") {
-                    (chosen_directory, "/")
-                } else {
+            // Stored directories use forward slashes, which work everywhere
+            None => if chosen_directory.contains("\\") && !chosen_directory.contains("/") {
                     (chosen_directory, "\\")
+                } else {
+                    (chosen_directory, "/")
                 }
         }
     }
 }
-
@@ -4,7 +4,7 @@ use super::*;
 
 #[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum ChosenFeatures {
-    Preset { preset_name: FeatureSet },
+    Preset { preset_name: PresetName },
     Custom { features: Vec<Feature> }
 }
 
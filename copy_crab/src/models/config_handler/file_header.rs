@@ -0,0 +1,82 @@
+use crate::models::Runtime;
+
+/// The doc comment an embedded TS file may start with, e.g.
+/// ```ts
+/// /** @headers | Parsers
+///  * @runtimes ["Deno", "ClientSide"]
+///  */
+/// ```
+/// Anything it leaves out falls back to the defaults of the feature
+#[derive(Debug, Default, Clone, PartialEq)]
+pub struct FileHeader {
+    /// What the feature is called in prompts and messages
+    pub display_name: Option<String>,
+    /// The runtimes the feature works on. `None` means all of them
+    pub runtimes: Option<Vec<Runtime>>,
+}
+
+impl FileHeader {
+    pub fn parse(ts_file: &str) -> Self {
+        let mut header = Self::default();
+        let Some(comment) = leading_doc_comment(ts_file) else {
+            return header;
+        };
+
+        for line in comment.lines() {
+            let line = line.trim_start().trim_start_matches('*').trim();
+            if let Some(rest) = line.strip_prefix("@headers") {
+                header.display_name = rest
+                    .split_once('|')
+                    .map(|(_, name)| name.trim().to_string())
+                    .filter(|name| !name.is_empty());
+            } else if let Some(rest) = line.strip_prefix("@runtimes") {
+                // A list that can't be read restricts nothing
+                header.runtimes = serde_json::from_str(rest.trim()).ok();
+            }
+        }
+
+        header
+    }
+
+    pub fn supports(&self, runtime: &Runtime) -> bool {
+        self.runtimes
+            .as_ref()
+            .is_none_or(|runtimes| runtimes.contains(runtime))
+    }
+}
+
+/// The inside of the `/** ... */` comment at the very top of the file
+fn leading_doc_comment(ts_file: &str) -> Option<&str> {
+    let rest = ts_file.trim_start().strip_prefix("/**")?;
+    let end = rest.find("*/")?;
+    Some(&rest[..end])
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn parses_name_and_runtimes() {
+        let header = FileHeader::parse("/** @headers | Parsers\n * @runtimes [\"Deno\", \"ClientSide\"]\n */\nexport {};\n");
+        assert_eq!(header, FileHeader {
+            display_name: Some("Parsers".to_string()),
+            runtimes: Some(vec![Runtime::Deno, Runtime::ClientSide]),
+        });
+        assert!(header.supports(&Runtime::Deno));
+        assert!(!header.supports(&Runtime::NodeJs));
+    }
+
+    #[test]
+    fn only_the_leading_comment_counts() {
+        assert_eq!(FileHeader::parse("const a = 1;\n/** @headers | Late */\n"), FileHeader::default());
+        assert_eq!(FileHeader::parse("// @headers | Line comment\n"), FileHeader::default());
+    }
+
+    #[test]
+    fn missing_parts_fall_back() {
+        let header = FileHeader::parse("\n/** @headers Nameless\n * @runtimes not a list\n */\n");
+        assert_eq!(header, FileHeader::default());
+        assert!(header.supports(&Runtime::NodeJs));
+    }
+}
@@ -1,13 +1,24 @@
+mod file_header;
+
 use std::collections::HashSet;
 
+use crate::models::Runtime;
+use crate::models::ts_imports::split_imports;
 use crate::ts_file_data;
+use file_header::FileHeader;
 
+use clap::ValueEnum;
 use serde::{Serialize, Deserialize};
 
 use strum::IntoEnumIterator;
 use strum_macros::{EnumIter, EnumString};
 
-#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, EnumIter, Eq, Hash, EnumString)]
+/// Names features used to be written under, e.g. `Core` before it was named
+/// after the file the other features import it from
+const PREVIOUS_FILE_NAMES: [&str; 1] = ["core.ts"];
+
+#[derive(Debug, PartialEq, Clone, Serialize, Deserialize, EnumIter, Eq, Hash, PartialOrd, Ord, EnumString, ValueEnum)]
+#[value(rename_all = "verbatim")]
 pub enum Feature {
     Core,
     Example,
@@ -27,16 +38,58 @@ impl Feature {
     pub fn get_complements(included: &Vec<Self>) -> Vec<Self> {
         let mut fin_set: HashSet<Self> = Self::iter().collect();
         for feature in included {
-            fin_set.remove(&feature);
+            fin_set.remove(feature);
         }
 
         fin_set.into_iter().collect()
     }
 
+    /// The metadata at the top of the feature's TS file
+    pub fn header(&self) -> FileHeader {
+        FileHeader::parse(self.get_implementation())
+    }
+
+    /// The name shown to people. Falls back to the name used on the command line
+    pub fn display_name(&self) -> String {
+        self.header()
+            .display_name
+            .unwrap_or_else(|| format!("{self:?}"))
+    }
+
+    pub fn supports(&self, runtime: &Runtime) -> bool {
+        self.header().supports(runtime)
+    }
+
+    /// The features this one imports from, going by its `./*.ts` imports
+    pub fn dependencies(&self) -> Vec<Self> {
+        split_imports(self.get_implementation())
+            .1
+            .iter()
+            .filter_map(|import| import.specifier.strip_prefix("./"))
+            .filter_map(|file_name| Self::iter().find(|feature| feature.get_file_name() == file_name))
+            .collect()
+    }
+
+    /// `features` followed by everything they depend on, directly or through
+    /// another feature, that isn't in `features` yet
+    pub fn with_dependencies(features: &[Self]) -> Vec<Self> {
+        let mut needed: HashSet<Self> = HashSet::new();
+        let mut pending = features.to_vec();
+        while let Some(feature) = pending.pop() {
+            if needed.insert(feature.clone()) {
+                pending.extend(feature.dependencies());
+            }
+        }
+
+        let implied = Self::iter().filter(|feature| needed.contains(feature) && !features.contains(feature));
+        features.iter().cloned().chain(implied).collect()
+    }
+
     pub fn get_file_name(&self) -> &str {
         use Feature as F;
         match self {
-            F::Core => "core.ts",
+            // Named after the file the other features import it from
+            F::Core => "core_functions.ts",
             F::Example => "example.ts",
             F::Option => "option.ts",
             F::Result => "result.ts",
@@ -44,6 +97,13 @@ impl Feature {
         }
     }
 
+    /// Whether a file in the `crabSafe` directory is one that copy_crab writes,
+    /// either now or under a name older versions used
+    pub fn is_file_name(file_name: &str) -> bool {
+        PREVIOUS_FILE_NAMES.contains(&file_name)
+            || Self::iter().any(|feature| feature.get_file_name() == file_name)
+    }
+
     pub fn get_implementation(&self) -> &'static str {
         // Alias them for quick reading
         use Feature as F;
@@ -59,3 +119,40 @@ impl Feature {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn sorted(mut features: Vec<Feature>) -> Vec<Feature> {
+        features.sort();
+        features
+    }
+
+    #[test]
+    fn dependencies_follow_the_local_imports() {
+        assert_eq!(Feature::Core.dependencies(), Vec::new());
+        assert_eq!(Feature::Example.dependencies(), vec![Feature::Result]);
+        assert_eq!(sorted(Feature::Parsers.dependencies()), vec![Feature::Core, Feature::Result]);
+    }
+
+    #[test]
+    fn with_dependencies_appends_what_is_implied() {
+        assert_eq!(
+            Feature::with_dependencies(&[Feature::Parsers]),
+            vec![Feature::Parsers, Feature::Core, Feature::Result]
+        );
+        assert_eq!(
+            Feature::with_dependencies(&[Feature::Example, Feature::Core]),
+            vec![Feature::Example, Feature::Core, Feature::Result]
+        );
+        assert_eq!(Feature::with_dependencies(&[Feature::Core]), vec![Feature::Core]);
+    }
+
+    #[test]
+    fn file_names_include_previous_ones() {
+        assert!(Feature::is_file_name("core_functions.ts"));
+        assert!(Feature::is_file_name("core.ts"));
+        assert!(!Feature::is_file_name("my_helpers.ts"));
+    }
+}
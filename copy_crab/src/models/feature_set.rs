@@ -1,7 +1,10 @@
-use serde::{Serialize, Deserialize};
+use std::{convert::Infallible, fmt, str::FromStr};
+
+use clap::ValueEnum;
+use serde::{Serialize, Deserialize, Deserializer};
 use super::Feature;
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ValueEnum)]
 pub enum FeatureSet {
     All,
     Core,
@@ -9,6 +12,17 @@ pub enum FeatureSet {
 }
 
 impl FeatureSet {
+    /// Accepts both the command-line names (`core-plus`) and the stored ones (`CorePlus`)
+    pub fn from_name(name: &str) -> Option<Self> {
+        Self::value_variants()
+            .iter()
+            .find(|feature_set| {
+                format!("{feature_set:?}").eq_ignore_ascii_case(name)
+                    || feature_set.to_possible_value().is_some_and(|value| value.matches(name, true))
+            })
+            .cloned()
+    }
+
     pub fn get_feature_list(&self) -> Vec<Feature> {
         use FeatureSet as FS;
         use Feature as F;
@@ -26,3 +40,83 @@ impl FeatureSet {
         }
     }
 }
+
+/// A built-in `FeatureSet` or the name of a preset defined in `copy-paste.json`
+/// or the user-level config. Stored as a plain string either way
+#[derive(Debug, Clone, PartialEq, Serialize)]
+#[serde(untagged)]
+pub enum PresetName {
+    BuiltIn(FeatureSet),
+    Custom(String),
+}
+
+impl PresetName {
+    /// Custom presets that aren't defined (anymore) have no features
+    pub fn get_feature_list(&self) -> Vec<Feature> {
+        match self {
+            PresetName::BuiltIn(feature_set) => feature_set.get_feature_list(),
+            PresetName::Custom(name) => crate::settings_finder::custom_presets()
+                .get(name)
+                .cloned()
+                .unwrap_or_default(),
+        }
+    }
+
+    pub fn exists(&self) -> bool {
+        match self {
+            PresetName::BuiltIn(_) => true,
+            PresetName::Custom(name) => crate::settings_finder::custom_presets().contains_key(name),
+        }
+    }
+}
+
+/// Built-in names win over custom presets with the same name
+impl FromStr for PresetName {
+    type Err = Infallible;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        Ok(match FeatureSet::from_name(name) {
+            Some(feature_set) => PresetName::BuiltIn(feature_set),
+            None => PresetName::Custom(name.to_string()),
+        })
+    }
+}
+
+/// Read through `FromStr`, so stored names resolve the same way as `--preset`
+impl<'de> Deserialize<'de> for PresetName {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let Ok(preset_name) = String::deserialize(deserializer)?.parse();
+        Ok(preset_name)
+    }
+}
+
+impl fmt::Display for PresetName {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            PresetName::BuiltIn(feature_set) => write!(f, "{feature_set:?}"),
+            PresetName::Custom(name) => write!(f, "{name}"),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn deserialized(name: &str) -> PresetName {
+        serde_json::from_value(serde_json::json!(name)).unwrap()
+    }
+
+    #[test]
+    fn stored_names_resolve_like_the_command_line() {
+        assert_eq!(deserialized("CorePlus"), PresetName::BuiltIn(FeatureSet::CorePlus));
+        assert_eq!(deserialized("core-plus"), PresetName::BuiltIn(FeatureSet::CorePlus));
+        assert_eq!(deserialized("mine"), PresetName::Custom("mine".to_string()));
+    }
+
+    #[test]
+    fn built_in_names_are_stored_as_before() {
+        let preset_name = PresetName::BuiltIn(FeatureSet::CorePlus);
+        assert_eq!(serde_json::to_value(&preset_name).unwrap(), serde_json::json!("CorePlus"));
+    }
+}
@@ -0,0 +1,78 @@
+use std::fmt;
+
+use colored::Colorize;
+use serde::Serialize;
+
+use super::Feature;
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FeatureStatus {
+    /// Identical to the implementation shipped with copy_crab
+    Intact,
+    /// Present, but edited locally
+    Modified,
+    /// Untouched since it was written, but copy_crab now ships a different version
+    Outdated,
+    /// Neither the file nor the section could be found
+    Missing,
+    /// Chosen, but never written for this runtime
+    Unsupported,
+}
+
+impl FeatureStatus {
+    pub fn compare(found: &str, expected: &str) -> Self {
+        if found == expected {
+            FeatureStatus::Intact
+        } else {
+            FeatureStatus::Modified
+        }
+    }
+
+    /// Looks for `section` inside a bundled file. If only its first line is
+    /// found, the section is assumed to have been edited in place
+    pub fn find_section(bundle: &str, section: &str) -> Self {
+        if bundle.contains(section) {
+            return FeatureStatus::Intact;
+        }
+
+        let first_line = section
+            .lines()
+            .find(|line| !line.trim().is_empty())
+            .unwrap_or_default();
+
+        if !first_line.is_empty() && bundle.contains(first_line) {
+            FeatureStatus::Modified
+        } else {
+            FeatureStatus::Missing
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FeatureReport {
+    pub feature: Feature,
+    pub path: String,
+    pub status: FeatureStatus,
+}
+
+impl fmt::Display for FeatureReport {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use FeatureStatus as FS;
+        let label = match self.status {
+            FS::Intact => "intact".green(),
+            FS::Modified => "modified".yellow(),
+            FS::Outdated => "outdated".cyan(),
+            FS::Missing => "missing".red(),
+            FS::Unsupported => "unsupported".dimmed(),
+        };
+
+        write!(
+            f,
+            "{:>12}  {:<8} {}",
+            label,
+            self.feature.display_name().bold(),
+            self.path
+        )
+    }
+}
@@ -0,0 +1,73 @@
+use std::{fmt, fs};
+
+use colored::Colorize;
+use serde::Serialize;
+
+use crate::settings_finder::hash;
+
+/// A file as copy_crab would write it
+#[derive(Debug, Clone, PartialEq)]
+pub struct GeneratedFile {
+    pub path: String,
+    pub contents: String,
+}
+
+impl GeneratedFile {
+    /// `written_hash` is what the lockfile recorded for this file, if anything
+    pub fn state_on_disk(&self, written_hash: Option<&str>) -> FileState {
+        match fs::read_to_string(&self.path) {
+            Ok(found) if found == self.contents => FileState::UpToDate,
+            Ok(found) => match written_hash {
+                Some(written_hash) if hash(&found) == written_hash => FileState::Outdated,
+                Some(_) => FileState::Edited,
+                None => FileState::Stale,
+            },
+            Err(_) => FileState::Missing,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FileState {
+    UpToDate,
+    /// Expected, but not on disk
+    Missing,
+    /// On disk with different contents, but there's no record of what was written
+    Stale,
+    /// Changed by hand since copy_crab wrote it
+    Edited,
+    /// Untouched since copy_crab wrote it, but copy_crab now ships different contents
+    Outdated,
+    /// On disk, but not expected
+    Extra,
+}
+
+impl FileState {
+    /// On disk, but not with the contents copy_crab would write
+    pub fn is_changed(&self) -> bool {
+        matches!(self, FileState::Stale | FileState::Edited | FileState::Outdated)
+    }
+}
+
+#[derive(Debug, Clone, Serialize)]
+pub struct FileDrift {
+    pub path: String,
+    pub state: FileState,
+}
+
+impl fmt::Display for FileDrift {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        use FileState as FS;
+        let label = match self.state {
+            FS::UpToDate => "up to date".green(),
+            FS::Missing => "missing".red(),
+            FS::Stale => "stale".yellow(),
+            FS::Edited => "edited".yellow(),
+            FS::Outdated => "outdated".cyan(),
+            FS::Extra => "extra".magenta(),
+        };
+
+        write!(f, "{:>12}  {}", label, self.path)
+    }
+}
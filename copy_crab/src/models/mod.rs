@@ -3,23 +3,35 @@ mod project_choices;
 mod config_handler;
 mod feature_set;
 mod chosen_features;
+mod generated_file;
+mod feature_status;
+mod planned_change;
+mod settings;
+mod ts_imports;
+mod ts_declarations;
 
+use clap::ValueEnum;
 use serde::{Serialize, Deserialize};
 
 pub use project_builder::ProjectBuilder;
-pub use project_choices::ProjectChoices;
+pub use project_choices::{HandEdits, ProjectChoices};
 pub use config_handler::Feature;
-pub use feature_set::FeatureSet;
+pub use feature_set::{FeatureSet, PresetName};
 pub use chosen_features::ChosenFeatures;
+pub use generated_file::{GeneratedFile, FileDrift, FileState};
+pub use planned_change::{PlannedChange, render_plan, write_atomically};
+pub use feature_status::{FeatureStatus, FeatureReport};
+pub use settings::{Settings, DEFAULT_INSTALLATION};
 
-#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, ValueEnum)]
 pub enum Runtime {
     Deno,
+    #[value(alias = "node")]
     NodeJs,
     ClientSide
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum Modularity {
     SingleFile,
     SplitFiles
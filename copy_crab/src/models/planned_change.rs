@@ -0,0 +1,115 @@
+use std::{fs, path::Path};
+
+use crate::error::{IoContext, Result};
+
+use colored::Colorize;
+use similar::TextDiff;
+
+/// A single file copy_crab is about to create, overwrite or delete.
+/// `None` means the file doesn't exist (before) or gets deleted (after)
+#[derive(Debug, Clone, PartialEq)]
+pub struct PlannedChange {
+    pub path: String,
+    pub before: Option<String>,
+    pub after: Option<String>,
+}
+
+impl PlannedChange {
+    pub fn write(path: String, contents: String) -> Self {
+        let before = fs::read_to_string(&path).ok();
+        Self { path, before, after: Some(contents) }
+    }
+
+    pub fn delete(path: String) -> Self {
+        let before = fs::read_to_string(&path).ok();
+        Self { path, before, after: None }
+    }
+
+    pub fn is_noop(&self) -> bool {
+        self.before == self.after
+    }
+
+    pub fn apply(&self) -> Result<()> {
+        match &self.after {
+            Some(contents) => {
+                if let Some(parent) = Path::new(&self.path).parent() {
+                    fs::create_dir_all(parent).at(parent)?;
+                }
+                write_atomically(Path::new(&self.path), contents.as_bytes())?;
+            },
+            None => if fs::metadata(&self.path).is_ok() {
+                fs::remove_file(&self.path).at(&self.path)?;
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Renders the change as a coloured unified diff
+    pub fn diff(&self) -> String {
+        let old_name = match self.before {
+            Some(_) => format!("a/{}", self.path),
+            None => "/dev/null".to_string(),
+        };
+        let new_name = match self.after {
+            Some(_) => format!("b/{}", self.path),
+            None => "/dev/null".to_string(),
+        };
+
+        let before = self.before.as_deref().unwrap_or_default();
+        let after = self.after.as_deref().unwrap_or_default();
+        let diff = TextDiff::from_lines(before, after)
+            .unified_diff()
+            .header(&old_name, &new_name)
+            .to_string();
+
+        diff.lines()
+            .map(|line| {
+                if line.starts_with("---") || line.starts_with("+++") {
+                    line.bold().to_string()
+                } else if line.starts_with("@@") {
+                    line.cyan().to_string()
+                } else if line.starts_with('+') {
+                    line.green().to_string()
+                } else if line.starts_with('-') {
+                    line.red().to_string()
+                } else {
+                    line.to_string()
+                }
+            })
+            .collect::<Vec<String>>()
+            .join("\n")
+    }
+}
+
+/// Writes into a temporary file next to `path` and renames it over `path`, so
+/// other tools (or a crash) never see a half-written file
+pub fn write_atomically(path: &Path, contents: &[u8]) -> Result<()> {
+    let file_name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp_path = path.with_file_name(format!(".{file_name}.{}.tmp", std::process::id()));
+
+    let res = fs::write(&temp_path, contents)
+        .and_then(|()| match fs::metadata(path) {
+            // Keep e.g. the executable bit of the file being replaced
+            Ok(meta) => fs::set_permissions(&temp_path, meta.permissions()),
+            Err(_) => Ok(()),
+        })
+        .and_then(|()| fs::rename(&temp_path, path));
+
+    if res.is_err() {
+        let _ = fs::remove_file(&temp_path);
+    }
+    res.at(path)
+}
+
+/// Renders every change of a plan one after the other
+pub fn render_plan(changes: &[PlannedChange]) -> String {
+    if changes.is_empty() {
+        return "No changes".truecolor(0, 220, 150).to_string();
+    }
+
+    changes.iter()
+        .map(|change| change.diff())
+        .collect::<Vec<String>>()
+        .join("\n")
+}
@@ -1,7 +1,9 @@
-use super::{Runtime, ProjectChoices, Modularity, ChosenFeatures};
+use super::{Runtime, ProjectChoices, Modularity, ChosenFeatures, DEFAULT_INSTALLATION};
+use crate::error::{CopyCrabError, Result};
 
-#[derive(Default)]
+#[derive(Default, Clone)]
 pub struct ProjectBuilder {
+    name: Option<String>,
     runtime: Option<Runtime>,
     chosen_directory: Option<String>,
     feature_set: Option<ChosenFeatures>,
@@ -13,6 +15,11 @@ impl ProjectBuilder {
         Self::default()
     }
 
+    pub fn set_name(mut self, name: String) -> Self {
+        self.name = Some(name);
+        self
+    }
+
     pub fn set_runtime(mut self, chosen_runtime: Runtime) -> Self {
         self.runtime = Some(chosen_runtime);
         self
@@ -33,23 +40,16 @@ impl ProjectBuilder {
         self
     }
 
-    pub fn build(self) -> ProjectChoices {
-        let Some(runtime) = self.runtime else {
-            panic!("Runtime not inserted");
-        };
-
-        let Some(chosen_directory) = self.chosen_directory else {
-            panic!("Chosen directory not inserted");
-        };
-
-        let Some(feature_set) = self.feature_set else {
-            panic!("Feature set not inserted");
-        };
+    pub fn build(self) -> Result<ProjectChoices> {
+        let missing = |choice: &str| CopyCrabError::InvalidCombination(format!("{choice} not chosen"));
 
-        let Some(modularity) = self.modularity else {
-            panic!("File modularity set not chosen");
-        };
+        let name = self.name.unwrap_or_else(|| DEFAULT_INSTALLATION.to_string());
+        let runtime = self.runtime.ok_or_else(|| missing("Runtime"))?;
+        let chosen_directory = self.chosen_directory.ok_or_else(|| missing("Directory"))?;
+        let chosen_directory = crate::settings_finder::relative_to_root(&chosen_directory);
+        let feature_set = self.feature_set.ok_or_else(|| missing("Feature set"))?;
+        let modularity = self.modularity.ok_or_else(|| missing("File modularity"))?;
 
-        ProjectChoices { runtime, chosen_directory, feature_set, modularity }
+        Ok(ProjectChoices { name, runtime, chosen_directory, feature_set, modularity })
     }
 }
\ No newline at end of file
@@ -1,98 +1,453 @@
 use serde::{Serialize, Deserialize};
 use super::*;
 use crate::{parse_path, ts_file_data::*};
+use crate::error::{CopyCrabError, IoContext, Result};
+use crate::settings_finder::{hash, LockedInstallation};
 
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ProjectChoices {
+    pub name: String,
     pub runtime: Runtime,
     pub chosen_directory: String,
     pub feature_set: ChosenFeatures,
     pub modularity: Modularity
 }
 
+/// What applying these choices does to files that were edited by hand since
+/// copy_crab wrote them. Edits to a file copy_crab would write the same way as
+/// before are kept, unless they are restored
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum HandEdits {
+    /// Fail instead of overwriting any of them
+    Keep,
+    /// Overwrite the ones copy_crab now writes differently
+    OverwriteChanged,
+    /// Overwrite every one of them, as `sync` does
+    Restore,
+}
+
 impl ProjectChoices {
-    pub fn handle(&self) -> anyhow::Result<()> {
-        // Convert the feature set to the list of strings to use
-        let relevant_files: Vec<(&str, String)> = self.feature_set
-            .get_feature_list()
+    /// Writes this installation to disk and returns what was changed
+    pub fn handle(&self, edits: HandEdits) -> Result<Vec<PlannedChange>> {
+        let changes = self.plan(edits)?;
+        let edited_files = self.edited_files()?;
+        if edits == HandEdits::Keep {
+            if let Some((path, _)) = edited_files.iter().find(|(_, contents_changed)| *contents_changed) {
+                return Err(CopyCrabError::Conflict(format!(
+                    "{path} was edited by hand and would be overwritten. Pass `--force` to overwrite it"
+                )));
+            }
+        }
+
+        for (path, contents_changed) in edited_files {
+            let message = if contents_changed || edits == HandEdits::Restore {
+                format!("{path} was edited by hand, those edits are overwritten")
+            } else {
+                format!("{path} was edited by hand and is left as it is. Run `copy_crab sync` to restore it")
+            };
+            crate::output::emit(crate::output::Event::Warning { message });
+        }
+
+        if changes.iter().any(|change| crate::settings_finder::is_host_file(&change.path)) {
+            crate::settings_finder::backup_outdated()?;
+        }
+
+        for change in &changes {
+            change.apply()?;
+        }
+
+        Ok(changes)
+    }
+
+    /// Everything `handle` would change on disk, including removing files that
+    /// are no longer needed and updating the settings file. Only files copy_crab
+    /// wrote are removed, anything else in the `crabSafe` directory stays
+    pub fn plan(&self, edits: HandEdits) -> Result<Vec<PlannedChange>> {
+        let kept_edits: Vec<String> = match edits {
+            HandEdits::Restore => Vec::new(),
+            HandEdits::Keep | HandEdits::OverwriteChanged => self.edited_files()?
+                .into_iter()
+                .filter(|(_, contents_changed)| !contents_changed)
+                .map(|(path, _)| path)
+                .collect(),
+        };
+
+        let mut changes: Vec<PlannedChange> = self.expected_files()?
+            .into_iter()
+            .filter(|file| !kept_edits.contains(&file.path))
+            .map(|file| PlannedChange::write(file.path, file.contents))
+            .collect();
+
+        let locked = crate::settings_finder::locked_installation(&self.name).unwrap_or_default();
+        let extra_files = self.find_drift()?
+            .into_iter()
+            .filter(|file| file.state == FileState::Extra)
+            .filter(|file| {
+                let file_name = std::path::Path::new(&file.path).file_name().unwrap_or_default();
+                locked.files.contains_key(&file.path) || Feature::is_file_name(&file_name.to_string_lossy())
+            })
+            .map(|file| PlannedChange::delete(file.path));
+        changes.extend(extra_files);
+
+        changes.push(crate::settings_finder::plan_save(self)?);
+        changes.push(crate::settings_finder::plan_lock(self)?);
+        changes.retain(|change| !change.is_noop());
+
+        Ok(changes)
+    }
+
+    /// The files edited by hand that applying these choices overwrites, unless
+    /// they are kept with `HandEdits::Keep`
+    pub fn overwritten_edits(&self) -> Result<Vec<String>> {
+        Ok(self.edited_files()?
+            .into_iter()
+            .filter(|(_, contents_changed)| *contents_changed)
+            .map(|(path, _)| path)
+            .collect())
+    }
+
+    /// The files edited by hand since copy_crab wrote them, and whether copy_crab
+    /// now writes something else to them than it did back then
+    fn edited_files(&self) -> Result<Vec<(String, bool)>> {
+        let expected = self.expected_files()?;
+        let locked = crate::settings_finder::locked_installation(&self.name).unwrap_or_default();
+
+        Ok(self.find_drift()?
+            .into_iter()
+            .filter(|file| file.state == FileState::Edited)
+            .map(|file| {
+                let contents_changed = expected
+                    .iter()
+                    .find(|expected| expected.path == file.path)
+                    .is_some_and(|expected| locked.files.get(&file.path) != Some(&hash(&expected.contents)));
+                (file.path, contents_changed)
+            })
+            .collect())
+    }
+
+    /// Renders every file this installation consists of, without touching the disk
+    /// Fails if the features can't be bundled into a single file together
+    pub fn expected_files(&self) -> Result<Vec<GeneratedFile>> {
+        let features = self.installed_features();
+
+        match self.modularity {
+            Modularity::SingleFile => Ok(vec![GeneratedFile {
+                path: self.feature_path(&Feature::Core),
+                contents: self.gen_single_filedata(&features)?,
+            }]),
+            Modularity::SplitFiles => Ok(features
+                .iter()
+                .map(|feature| GeneratedFile {
+                    path: self.feature_path(feature),
+                    contents: self.split_file_contents(feature),
+                })
+                .collect())
+        }
+    }
+
+    /// The chosen features together with the ones they depend on. Features the
+    /// runtime doesn't support bring nothing along, since none of their code is written
+    pub fn features(&self) -> Vec<Feature> {
+        let chosen = self.feature_set.get_feature_list();
+        let supported: Vec<Feature> = chosen
             .iter()
-            .map(|feature| (feature.get_implementation(), feature.get_file_name().to_string()))
-            .collect::<Vec<(&str, String)>>();
+            .filter(|feature| self.supports(feature))
+            .cloned()
+            .collect();
+
+        let implied = Feature::with_dependencies(&supported)
+            .into_iter()
+            .filter(|feature| !chosen.contains(feature));
+        chosen.iter().cloned().chain(implied).collect()
+    }
+
+    /// The features that actually get written for this runtime
+    pub fn installed_features(&self) -> Vec<Feature> {
+        self.features()
+            .into_iter()
+            .filter(|feature| self.supports(feature))
+            .collect()
+    }
+
+    /// The installed features that import from `feature`
+    pub fn needed_by(&self, feature: &Feature) -> Vec<Feature> {
+        self.installed_features()
+            .into_iter()
+            .filter(|dependent| dependent.dependencies().contains(feature))
+            .collect()
+    }
+
+    /// These choices without `removed`. Features that were only pulled in as
+    /// dependencies go away once nothing needs them anymore. Fails if a feature
+    /// that stays depends on one of them, or if nothing would be left
+    pub fn without(&self, removed: &[Feature]) -> Result<ProjectChoices> {
+        let chosen = self.feature_set.get_feature_list();
+        let features: Vec<Feature> = Feature::get_all()
+            .into_iter()
+            .filter(|feature| chosen.contains(feature) && !removed.contains(feature))
+            .collect();
+
+        if features.is_empty() {
+            // It doesn't make sense to have an packageless version of this
+            return Err(CopyCrabError::InvalidCombination(
+                "Removing every feature would leave nothing installed".to_string()
+            ));
+        }
 
+        let mut updated_choices = self.clone();
+        updated_choices.feature_set = ChosenFeatures::Custom { features };
+
+        if let Some(needed) = removed.iter().find(|feature| updated_choices.features().contains(feature)) {
+            let dependents: Vec<String> = updated_choices
+                .needed_by(needed)
+                .iter()
+                .map(Feature::display_name)
+                .collect();
+            return Err(CopyCrabError::Conflict(format!(
+                "{} is needed by {}. Remove {} as well",
+                needed.display_name(),
+                dependents.join(", "),
+                if dependents.len() == 1 { "it" } else { "them" },
+            )));
+        }
+
+        Ok(updated_choices)
+    }
+
+    pub fn supports(&self, feature: &Feature) -> bool {
+        feature.supports(&self.runtime)
+    }
+
+    /// The file a feature's implementation lives in. For `SingleFile`
+    /// installations that's the same file for every feature
+    pub fn feature_path(&self, feature: &Feature) -> String {
         let (dir_path, sep) = parse_path(&self.chosen_directory);
         match self.modularity {
-            Modularity::SingleFile => {
-                // We don't need the file names of the files so 🤷‍♀️
-                let relevant_files: Vec<&str> = relevant_files
-                    .iter()
-                    .filter(|t| {
-                        if self.runtime != Runtime::Deno && t.1 == "parsers.ts" {
-                            return false;
-                        }
-
-                        true
-                    })
-                    .map(|t| t.0)
-                    .collect();
-                let fin_string = self.gen_single_filedata(relevant_files);
-
-                let fin_file = format!("{dir_path}{sep}crabSafe.ts");
-                std::fs::write(fin_file, fin_string)?;
-            },
-            Modularity::SplitFiles => {
-                let fin_dir = format!("{dir_path}{sep}crabSafe");
-                std::fs::create_dir(&fin_dir)?;
-                for (file_content, file_name) in relevant_files {
-                    let fin_file = format!("{fin_dir}{sep}{file_name}");
-                    std::fs::write(fin_file, file_content)?
+            Modularity::SingleFile => format!("{dir_path}{sep}crabSafe.ts"),
+            Modularity::SplitFiles => format!("{dir_path}{sep}crabSafe{sep}{}", feature.get_file_name()),
+        }
+    }
+
+    /// Checks every chosen feature against the implementation embedded in copy_crab.
+    /// For `SingleFile` installations each feature's section is looked up in the bundle,
+    /// the way `gen_single_filedata` wrote it
+    pub fn feature_status(&self) -> Vec<FeatureReport> {
+        let locked = crate::settings_finder::locked_installation(&self.name).unwrap_or_default();
+        let sections = match self.modularity {
+            // Features that can't be bundled are looked for in full
+            Modularity::SingleFile => self.bundle(&self.installed_features())
+                .map(|bundle| bundle.sections)
+                .unwrap_or_default(),
+            Modularity::SplitFiles => Vec::new(),
+        };
+
+        self.features()
+            .into_iter()
+            .map(|feature| {
+                let path = self.feature_path(&feature);
+                let status = if !self.supports(&feature) {
+                    FeatureStatus::Unsupported
+                } else if self.is_outdated(&feature, &path, &locked) {
+                    FeatureStatus::Outdated
+                } else {
+                    match (&self.modularity, std::fs::read_to_string(&path)) {
+                        (_, Err(_)) => FeatureStatus::Missing,
+                        (Modularity::SplitFiles, Ok(found)) => FeatureStatus::compare(
+                            &found,
+                            &self.split_file_contents(&feature),
+                        ),
+                        (Modularity::SingleFile, Ok(found)) => {
+                            let section = sections
+                                .iter()
+                                .find(|(bundled, _)| *bundled == feature)
+                                .map_or_else(|| ts_imports::split_imports(feature.get_implementation()).0, |(_, section)| section.clone());
+                            FeatureStatus::find_section(&found, section.trim())
+                        },
+                    }
+                };
+
+                FeatureReport { feature, path, status }
+            })
+            .collect()
+    }
+
+    /// Whether the file `feature` lives in is exactly as copy_crab wrote it,
+    /// while the implementation shipped for `feature` has changed since
+    fn is_outdated(&self, feature: &Feature, path: &str, locked: &LockedInstallation) -> bool {
+        let unchanged_since_written = std::fs::read_to_string(path)
+            .is_ok_and(|found| locked.files.get(path) == Some(&hash(&found)));
+        let shipped_differently = locked.features
+            .get(feature)
+            .is_some_and(|source_hash| *source_hash != hash(feature.get_implementation()));
+
+        unchanged_since_written && shipped_differently
+    }
+
+    /// Compares the expected files with what is currently on disk. Files inside
+    /// the `crabSafe` directory that no feature accounts for are reported as extra
+    pub fn find_drift(&self) -> Result<Vec<FileDrift>> {
+        let expected = self.expected_files()?;
+        let locked = crate::settings_finder::locked_installation(&self.name).unwrap_or_default();
+        let mut drift: Vec<FileDrift> = expected
+            .iter()
+            .map(|file| FileDrift {
+                path: file.path.clone(),
+                state: file.state_on_disk(locked.files.get(&file.path).map(String::as_str)),
+            })
+            .collect();
+
+        if let Modularity::SplitFiles = self.modularity {
+            let (dir_path, sep) = parse_path(&self.chosen_directory);
+            let fin_dir = format!("{dir_path}{sep}crabSafe");
+
+            if std::fs::metadata(&fin_dir).is_ok() {
+                for entry in std::fs::read_dir(&fin_dir).at(&fin_dir)? {
+                    let entry = entry.at(&fin_dir)?;
+                    if !entry.file_type().at(entry.path())?.is_file() {
+                        continue;
+                    }
+
+                    let file_name = entry.file_name().to_string_lossy().to_string();
+                    let path = format!("{fin_dir}{sep}{file_name}");
+
+                    if !expected.iter().any(|file| file.path == path) {
+                        drift.push(FileDrift { path, state: FileState::Extra });
+                    }
                 }
             }
         }
 
-        crate::settings_finder::save_settings(&self)?;
-    
-        Ok(())
+        Ok(drift)
     }
 
-    fn gen_single_filedata(&self, relevant_files: Vec<&str>) -> String {
-        let mut implementation_str = String::with_capacity(CORE_FUNCTIONS.len());
-        let mut import_lines = Vec::new();
-        for ts_file in relevant_files {
-            let filtered: Vec<&str> = ts_file.split("\n")
-                .filter(|line| {
-                    if line.trim().starts_with("import") {
-                        import_lines.push(line.to_string());
-                        return false;
-                    }
-                    true
-                })
-                .collect();
-            implementation_str.push_str(&filtered.join("\n"));
+    /// Rebuilds exactly what these choices describe: missing and stale files are
+    /// rewritten and extra files copy_crab wrote are removed. Returns what was
+    /// found beforehand
+    pub fn sync(&self) -> Result<Vec<FileDrift>> {
+        let drift = self.find_drift()?;
+
+        if drift.iter().any(|file| file.state != FileState::UpToDate) {
+            self.handle(HandEdits::Restore)?;
+        }
+
+        Ok(drift)
+    }
+
+    /// The feature's file in a `SplitFiles` installation, without the imports
+    /// the runtime can't resolve
+    fn split_file_contents(&self, feature: &Feature) -> String {
+        if self.runtime == Runtime::Deno {
+            return feature.get_implementation().to_string();
+        }
+
+        ts_imports::remove_imports(feature.get_implementation(), ts_imports::Import::is_url)
+    }
+
+    /// Splits the features into what goes into a `SingleFile` bundle.
+    /// Declarations another feature already made the same way are left out
+    /// of the later sections
+    fn bundle(&self, features: &[Feature]) -> Result<Bundle> {
+        let mut sections = Vec::with_capacity(features.len());
+        let mut imports = Vec::new();
+        let mut declared_names = ts_declarations::DeclaredNames::default();
+        for feature in features {
+            let (section, file_imports) = ts_imports::split_imports(feature.get_implementation());
+            imports.extend(file_imports);
+            sections.push((feature.clone(), declared_names.add(&feature.display_name(), &section)?));
         }
 
         if self.runtime != Runtime::Deno {
             // Get rid of deno imports
-            import_lines = filter_out(import_lines, "http");
+            imports.retain(|import| !import.is_url());
         }
 
-        // Remove local imports
-        let import_lines = filter_out(import_lines, "\"./").join("\n");
+        // Remove local imports, everything they point at is in this file
+        imports.retain(|import| !import.is_local());
+        Ok(Bundle { sections, imports })
+    }
+
+    fn gen_single_filedata(&self, features: &[Feature]) -> Result<String> {
+        let bundle = self.bundle(features)?;
+        let mut implementation_str = String::with_capacity(CORE_FUNCTIONS.len());
+        for (_, section) in &bundle.sections {
+            implementation_str.push_str(section);
+        }
+
+        let import_lines = ts_imports::merge(bundle.imports).join("\n");
 
         // Add new lines if meaningful
-        let import_lines = if import_lines.trim().len() == 0 {
-            format!("")
+        let import_lines = if import_lines.trim().is_empty() {
+            String::new()
         } else {
             format!("{import_lines}\n\n")
         };
 
-        format!("{import_lines}{}", implementation_str.trim())
+        Ok(format!("{import_lines}{}", implementation_str.trim()))
     }
 }
 
-/// Filters out lines that contain the `exclude` string in the parameter
-fn filter_out(lines: Vec<String>, exclude: &str) -> Vec<String> {
-    lines.iter()
-        .filter(|line| !line.contains(exclude))
-        .map(|s| s.to_string())
-        .collect()
+/// The parts of a `SingleFile` installation, before they are put together
+struct Bundle {
+    /// Each feature's code, in the order it's written
+    sections: Vec<(Feature, String)>,
+    imports: Vec<ts_imports::Import>,
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn choices(runtime: Runtime, features: &[Feature]) -> ProjectChoices {
+        ProjectChoices {
+            name: "default".to_string(),
+            runtime,
+            chosen_directory: "src".to_string(),
+            feature_set: ChosenFeatures::Custom { features: features.to_vec() },
+            modularity: Modularity::SplitFiles,
+        }
+    }
+
+    #[test]
+    fn without_refuses_to_remove_what_others_need() {
+        let choices = choices(Runtime::Deno, &[Feature::Result, Feature::Parsers]);
+
+        let Err(CopyCrabError::Conflict(message)) = choices.without(&[Feature::Result]) else {
+            panic!("expected a conflict");
+        };
+        assert_eq!(message, "Result is needed by Parsers. Remove it as well");
+    }
+
+    #[test]
+    fn without_removes_dependents_together() {
+        let choices = choices(Runtime::Deno, &[Feature::Option, Feature::Result, Feature::Parsers]);
+        let updated_choices = choices.without(&[Feature::Result, Feature::Parsers]).unwrap();
+
+        assert_eq!(updated_choices.features(), vec![Feature::Option, Feature::Core]);
+    }
+
+    #[test]
+    fn without_everything_fails() {
+        let choices = choices(Runtime::Deno, &[Feature::Option]);
+        assert!(matches!(
+            choices.without(&[Feature::Option, Feature::Core]),
+            Err(CopyCrabError::InvalidCombination(_))
+        ));
+    }
+
+    #[test]
+    fn without_drops_dependencies_nothing_needs() {
+        let choices = choices(Runtime::Deno, &[Feature::Option, Feature::Parsers]);
+        let updated_choices = choices.without(&[Feature::Parsers]).unwrap();
+
+        assert_eq!(updated_choices.feature_set, ChosenFeatures::Custom { features: vec![Feature::Option] });
+        assert_eq!(updated_choices.features(), vec![Feature::Option, Feature::Core]);
+    }
+
+    #[test]
+    fn unsupported_features_bring_nothing_along() {
+        let choices = choices(Runtime::NodeJs, &[Feature::Option, Feature::Parsers]);
+
+        assert_eq!(choices.features(), vec![Feature::Option, Feature::Parsers, Feature::Core]);
+        assert_eq!(choices.installed_features(), vec![Feature::Option, Feature::Core]);
+    }
 }
@@ -0,0 +1,107 @@
+use std::collections::BTreeMap;
+
+use serde::{Serialize, Deserialize};
+
+use super::{ChosenFeatures, Feature, PresetName, ProjectChoices};
+use crate::error::{CopyCrabError, Result};
+
+/// The name an installation gets when nobody chose one
+pub const DEFAULT_INSTALLATION: &str = "default";
+
+/// Everything stored under the `crabSafe` key. A project can hold several
+/// installations, e.g. one per package of a monorepo
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Settings {
+    pub installations: Vec<ProjectChoices>,
+    /// Presets the whole team can choose from, by name
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    pub presets: BTreeMap<String, Vec<Feature>>,
+}
+
+impl Settings {
+    pub fn get(&self, name: &str) -> Option<&ProjectChoices> {
+        self.installations.iter().find(|choices| choices.name == name)
+    }
+
+    pub fn names(&self) -> Vec<String> {
+        self.installations.iter().map(|choices| choices.name.clone()).collect()
+    }
+
+    /// Picks the installation called `name`. Without a name, there has to be
+    /// exactly one installation to pick
+    pub fn select(&self, name: Option<&str>) -> Result<&ProjectChoices> {
+        match (name, self.installations.as_slice()) {
+            (Some(name), _) => self.get(name).ok_or_else(|| CopyCrabError::Conflict(format!(
+                "There is no installation called {name}. Found: {}", self.names().join(", ")
+            ))),
+            (None, [only]) => Ok(only),
+            (None, []) => Err(CopyCrabError::NotInstalled),
+            (None, _) => Err(CopyCrabError::InvalidCombination(format!(
+                "This project has several installations ({}). Pick one with `--installation`",
+                self.names().join(", ")
+            ))),
+        }
+    }
+
+    /// The first installation that uses a preset nobody defined
+    pub fn check_presets(&self) -> Result<()> {
+        for choices in &self.installations {
+            if let ChosenFeatures::Preset { preset_name } = &choices.feature_set {
+                if !preset_name.exists() {
+                    return Err(CopyCrabError::Conflict(format!(
+                        "The installation {} uses the preset {preset_name}, which isn't defined anywhere",
+                        choices.name
+                    )));
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Installations using a preset from the user-level config get its
+    /// features instead, since nobody else can look that preset up
+    pub fn inline_user_presets(&mut self) {
+        for choices in &mut self.installations {
+            let ChosenFeatures::Preset { preset_name: preset_name @ PresetName::Custom(name) } = &choices.feature_set else {
+                continue;
+            };
+            if self.presets.contains_key(name) || !preset_name.exists() {
+                continue;
+            }
+
+            choices.feature_set = ChosenFeatures::Custom { features: preset_name.get_feature_list() };
+        }
+    }
+
+    /// Adds `choices`, or replaces the installation with the same name
+    pub fn upsert(&mut self, choices: &ProjectChoices) {
+        match self.installations.iter_mut().find(|found| found.name == choices.name) {
+            Some(found) => *found = choices.clone(),
+            None => self.installations.push(choices.clone()),
+        }
+    }
+
+    pub fn remove(&mut self, name: &str) {
+        self.installations.retain(|choices| choices.name != name);
+    }
+
+    /// Makes sure `choices` can be added without two installations
+    /// writing into the same place
+    pub fn check_new(&self, choices: &ProjectChoices) -> Result<()> {
+        if self.get(&choices.name).is_some() {
+            return Err(CopyCrabError::Conflict(format!(
+                "An installation called {} already exists. Use `add` or `remove` to modify it",
+                choices.name
+            )));
+        }
+
+        if let Some(other) = self.installations.iter().find(|other| other.chosen_directory == choices.chosen_directory) {
+            return Err(CopyCrabError::Conflict(format!(
+                "The installation {} already uses {}", other.name, other.chosen_directory
+            )));
+        }
+
+        Ok(())
+    }
+}
@@ -0,0 +1,227 @@
+use std::collections::BTreeMap;
+
+use crate::error::{CopyCrabError, Result};
+
+use oxc_allocator::Allocator;
+use oxc_ast::ast::{Declaration, ExportDefaultDeclarationKind, Program, Statement};
+use oxc_parser::Parser;
+use oxc_span::{GetSpan, SourceType, Span};
+
+use super::ts_imports::whole_lines;
+
+/// TypeScript keeps values and types apart, so `type Result` and
+/// `const Result` can live side by side
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+enum Space {
+    Value,
+    Type,
+}
+
+/// A name declared at the top level of a file
+#[derive(Debug, Clone, PartialEq, Eq, PartialOrd, Ord)]
+struct DeclaredName {
+    name: String,
+    space: Space,
+}
+
+/// The statements one source declares a name with. Overloads make it more than one
+#[derive(Debug)]
+struct Declared {
+    source: String,
+    statements: Vec<String>,
+    /// Interfaces and namespaces are merged by TypeScript, so they may differ
+    mergeable: bool,
+}
+
+/// The top-level names of everything that went into a single file so far
+#[derive(Debug, Default)]
+pub struct DeclaredNames {
+    declared: BTreeMap<DeclaredName, Declared>,
+}
+
+impl DeclaredNames {
+    /// Adds the declarations of `section`, which comes from `source`. Returns
+    /// `section` without the ones an earlier source already made in exactly
+    /// the same way, or fails if an earlier source declared a name differently
+    pub fn add(&mut self, source: &str, section: &str) -> Result<String> {
+        let allocator = Allocator::default();
+        let parsed = Parser::new(&allocator, section, SourceType::ts()).parse();
+        if parsed.panicked || !parsed.errors.is_empty() {
+            // Nothing to compare, tsc will point at the problem instead
+            return Ok(section.to_string());
+        }
+
+        let mut own: BTreeMap<DeclaredName, Declared> = BTreeMap::new();
+        let mut spans: BTreeMap<DeclaredName, Vec<Span>> = BTreeMap::new();
+        for statement in &parsed.program.body {
+            for (declared_name, mergeable) in declared_names(statement) {
+                let entry = own.entry(declared_name.clone()).or_insert_with(|| Declared {
+                    source: source.to_string(),
+                    statements: Vec::new(),
+                    mergeable: true,
+                });
+                entry.statements.push(statement.span().source_text(section).to_string());
+                entry.mergeable &= mergeable;
+                spans.entry(declared_name).or_default().push(with_leading_comments(&parsed.program, statement.span()));
+            }
+        }
+
+        let mut duplicates: Vec<Span> = Vec::new();
+        for (declared_name, declared) in own {
+            let Some(earlier) = self.declared.get(&declared_name) else {
+                self.declared.insert(declared_name, declared);
+                continue;
+            };
+
+            if earlier.statements == declared.statements {
+                duplicates.extend(spans.remove(&declared_name).unwrap_or_default());
+            } else if !(earlier.mergeable && declared.mergeable) {
+                return Err(CopyCrabError::Conflict(format!(
+                    "{} and {} both declare `{}`, but differently, so they can't share a single file. Use separate files instead",
+                    earlier.source,
+                    declared.source,
+                    declared_name.name,
+                )));
+            }
+        }
+
+        // A class is a value and a type, so it can show up twice
+        duplicates.sort_by_key(|span| span.start);
+        duplicates.dedup();
+
+        let mut fin_str = String::with_capacity(section.len());
+        let mut copied_up_to = 0;
+        for span in duplicates {
+            let taken_out = whole_lines(section, span);
+            fin_str.push_str(&section[copied_up_to..taken_out.start.max(copied_up_to)]);
+            copied_up_to = taken_out.end;
+        }
+        fin_str.push_str(&section[copied_up_to..]);
+
+        Ok(fin_str)
+    }
+}
+
+/// The names `statement` declares, and whether TypeScript would merge them
+/// with another declaration of the same name
+fn declared_names(statement: &Statement) -> Vec<(DeclaredName, bool)> {
+    let declaration = match statement {
+        Statement::ExportNamedDeclaration(export) => export.declaration.as_ref(),
+        Statement::ExportDefaultDeclaration(export) => {
+            let space = match export.declaration {
+                ExportDefaultDeclarationKind::TSInterfaceDeclaration(_) => Space::Type,
+                _ => Space::Value,
+            };
+            return vec![(DeclaredName { name: "default".to_string(), space }, false)];
+        },
+        _ => statement.as_declaration(),
+    };
+
+    let value = |name: &str| DeclaredName { name: name.to_string(), space: Space::Value };
+    let type_ = |name: &str| DeclaredName { name: name.to_string(), space: Space::Type };
+
+    match declaration {
+        Some(Declaration::VariableDeclaration(variables)) => variables.declarations
+            .iter()
+            .flat_map(|declarator| declarator.id.get_binding_identifiers())
+            .map(|id| (value(&id.name), false))
+            .collect(),
+        Some(Declaration::FunctionDeclaration(function)) => function.id
+            .iter()
+            .map(|id| (value(&id.name), false))
+            .collect(),
+        Some(Declaration::ClassDeclaration(class)) => class.id
+            .iter()
+            .flat_map(|id| [(value(&id.name), false), (type_(&id.name), false)])
+            .collect(),
+        Some(Declaration::TSEnumDeclaration(enum_)) => vec![
+            (value(&enum_.id.name), false),
+            (type_(&enum_.id.name), false),
+        ],
+        Some(Declaration::TSTypeAliasDeclaration(alias)) => vec![(type_(&alias.id.name), false)],
+        Some(Declaration::TSInterfaceDeclaration(interface)) => vec![(type_(&interface.id.name), true)],
+        Some(Declaration::TSModuleDeclaration(module)) => vec![
+            (value(&module.id.to_string()), true),
+            (type_(&module.id.to_string()), true),
+        ],
+        _ => Vec::new(),
+    }
+}
+
+/// `span` together with the doc comments right in front of it
+fn with_leading_comments(program: &Program, span: Span) -> Span {
+    let start = program.comments
+        .iter()
+        .filter(|comment| comment.attached_to == span.start && comment.span.end <= span.start)
+        .map(|comment| comment.span.start)
+        .min()
+        .unwrap_or(span.start);
+
+    Span::new(start, span.end)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn first_source_is_kept_as_is() {
+        let mut declared_names = DeclaredNames::default();
+        let section = "export const a = 1;\nfunction b() {}\n";
+        assert_eq!(declared_names.add("A", section).unwrap(), section);
+    }
+
+    #[test]
+    fn identical_declarations_are_left_out_with_their_comments() {
+        let mut declared_names = DeclaredNames::default();
+        declared_names.add("A", "/** Shared */\nexport type Shared = string;\n").unwrap();
+
+        let section = "const own = 1;\n\n/** Shared */\nexport type Shared = string;\nconst more = 2;\n";
+        assert_eq!(declared_names.add("B", section).unwrap(), "const own = 1;\n\nconst more = 2;\n");
+    }
+
+    #[test]
+    fn classes_are_left_out_once() {
+        let mut declared_names = DeclaredNames::default();
+        declared_names.add("A", "class C {}\n").unwrap();
+        assert_eq!(declared_names.add("B", "class C {}\nconst d = 1;\n").unwrap(), "const d = 1;\n");
+    }
+
+    #[test]
+    fn different_declarations_conflict() {
+        let mut declared_names = DeclaredNames::default();
+        declared_names.add("A", "export const value = 1;\n").unwrap();
+
+        let Err(CopyCrabError::Conflict(message)) = declared_names.add("B", "export const value = 2;\n") else {
+            panic!("expected a conflict");
+        };
+        assert!(message.contains("A and B both declare `value`"), "{message}");
+    }
+
+    #[test]
+    fn values_and_types_dont_clash() {
+        let mut declared_names = DeclaredNames::default();
+        declared_names.add("A", "type Result = string;\n").unwrap();
+
+        let section = "const Result = 1;\n";
+        assert_eq!(declared_names.add("B", section).unwrap(), section);
+    }
+
+    #[test]
+    fn interfaces_and_namespaces_merge() {
+        let mut declared_names = DeclaredNames::default();
+        declared_names.add("A", "interface I { a: string }\nnamespace N { export const a = 1; }\n").unwrap();
+
+        let section = "interface I { b: string }\nnamespace N { export const b = 1; }\n";
+        assert_eq!(declared_names.add("B", section).unwrap(), section);
+    }
+
+    #[test]
+    fn broken_sections_are_left_alone() {
+        let mut declared_names = DeclaredNames::default();
+        declared_names.add("A", "const a = 1;\n").unwrap();
+
+        let section = "const a = 1;\nconst = ;\n";
+        assert_eq!(declared_names.add("B", section).unwrap(), section);
+    }
+}
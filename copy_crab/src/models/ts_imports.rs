@@ -0,0 +1,347 @@
+use std::collections::{BTreeMap, BTreeSet};
+use std::ops::Range;
+
+use oxc_allocator::Allocator;
+use oxc_ast::ast::{ImportDeclaration, ImportDeclarationSpecifier, Statement};
+use oxc_parser::Parser;
+use oxc_span::{GetSpan, SourceType, Span};
+
+/// One `import` statement of a TS file, or the type-only half of one.
+/// `import { type A, b } from "x"` is split into two of these
+#[derive(Debug, Clone, PartialEq)]
+pub struct Import {
+    pub specifier: String,
+    pub type_only: bool,
+    pub default: Option<String>,
+    pub namespace: Option<String>,
+    /// The names between the braces, including their `as` renames
+    pub named: Vec<String>,
+}
+
+impl Import {
+    /// Imports of the other crabSafe files
+    pub fn is_local(&self) -> bool {
+        self.specifier.starts_with("./") || self.specifier.starts_with("../")
+    }
+
+    /// Imports that only Deno can fetch
+    pub fn is_url(&self) -> bool {
+        self.specifier.starts_with("http://") || self.specifier.starts_with("https://")
+    }
+
+    fn is_side_effect(&self) -> bool {
+        self.default.is_none() && self.namespace.is_none() && self.named.is_empty()
+    }
+}
+
+/// Everything imported from one specifier, either as values or as types
+#[derive(Debug, Default, Clone)]
+struct Merged {
+    defaults: BTreeSet<String>,
+    namespaces: BTreeSet<String>,
+    named: BTreeSet<String>,
+    side_effect: bool,
+}
+
+impl Merged {
+    /// The names that `other` doesn't import already
+    fn without(&self, other: &Merged) -> Merged {
+        Merged {
+            defaults: &self.defaults - &other.defaults,
+            namespaces: &self.namespaces - &other.namespaces,
+            named: &self.named - &other.named,
+            side_effect: self.side_effect,
+        }
+    }
+}
+
+/// Separates the import declarations of a TS file from the rest of it, going
+/// by its syntax tree. Imports with attributes are left in the code, and so is
+/// everything in a file that doesn't parse
+pub fn split_imports(ts_file: &str) -> (String, Vec<Import>) {
+    cut_imports(ts_file, |_| true)
+}
+
+/// The TS file without the import declarations `unwanted` picks, e.g. the URL
+/// imports on a runtime that can't fetch them. Everything else is kept as is
+pub fn remove_imports(ts_file: &str, unwanted: impl Fn(&Import) -> bool) -> String {
+    cut_imports(ts_file, |imports| imports.iter().all(&unwanted)).0
+}
+
+/// Cuts out the import declarations `cut` is true for, and returns them
+fn cut_imports(ts_file: &str, cut: impl Fn(&[Import]) -> bool) -> (String, Vec<Import>) {
+    let allocator = Allocator::default();
+    let parsed = Parser::new(&allocator, ts_file, SourceType::ts()).parse();
+    if parsed.panicked || !parsed.errors.is_empty() {
+        return (ts_file.to_string(), Vec::new());
+    }
+
+    let mut code = String::with_capacity(ts_file.len());
+    let mut imports = Vec::new();
+    let mut copied_up_to = 0;
+    for statement in &parsed.program.body {
+        let Statement::ImportDeclaration(declaration) = statement else {
+            continue;
+        };
+        if declaration.with_clause.is_some() || declaration.phase.is_some() {
+            continue;
+        }
+
+        let declared = from_declaration(ts_file, declaration);
+        if !cut(&declared) {
+            continue;
+        }
+
+        imports.extend(declared);
+        let taken_out = whole_lines(ts_file, declaration.span);
+        code.push_str(&ts_file[copied_up_to..taken_out.start]);
+        copied_up_to = taken_out.end;
+    }
+    code.push_str(&ts_file[copied_up_to..]);
+
+    (code, imports)
+}
+
+/// One statement per specifier and kind, sorted by specifier. Value imports
+/// come before type imports, and types that are also imported as values are
+/// left out
+pub fn merge(imports: Vec<Import>) -> Vec<String> {
+    let mut merged: BTreeMap<(String, bool), Merged> = BTreeMap::new();
+    for import in imports {
+        let entry = merged.entry((import.specifier.clone(), import.type_only)).or_default();
+        entry.side_effect |= import.is_side_effect();
+        entry.defaults.extend(import.default);
+        entry.namespaces.extend(import.namespace);
+        entry.named.extend(import.named);
+    }
+
+    let mut lines = Vec::new();
+    for ((specifier, type_only), imported) in &merged {
+        if !type_only {
+            lines.extend(render_values(specifier, imported));
+            continue;
+        }
+
+        let types = match merged.get(&(specifier.clone(), false)) {
+            Some(values) => imported.without(values),
+            None => imported.clone(),
+        };
+        lines.extend(render_types(specifier, &types));
+    }
+
+    lines
+}
+
+fn render_values(specifier: &str, imported: &Merged) -> Vec<String> {
+    let mut defaults = imported.defaults.iter();
+    let mut lines = Vec::new();
+
+    if !imported.named.is_empty() {
+        let named = format!("{{ {} }}", imported.named.iter().cloned().collect::<Vec<_>>().join(", "));
+        let clause = match defaults.next() {
+            Some(default) => format!("{default}, {named}"),
+            None => named,
+        };
+        lines.push(format!("import {clause} from \"{specifier}\";"));
+    }
+    lines.extend(defaults.map(|default| format!("import {default} from \"{specifier}\";")));
+    lines.extend(imported.namespaces.iter().map(|namespace| format!("import * as {namespace} from \"{specifier}\";")));
+
+    // Any other import of the specifier runs the module just the same
+    if lines.is_empty() && imported.side_effect {
+        lines.push(format!("import \"{specifier}\";"));
+    }
+
+    lines
+}
+
+/// A type-only import can't have a default and named imports at once
+fn render_types(specifier: &str, imported: &Merged) -> Vec<String> {
+    let mut lines: Vec<String> = imported.defaults
+        .iter()
+        .map(|default| format!("import type {default} from \"{specifier}\";"))
+        .collect();
+    lines.extend(imported.namespaces.iter().map(|namespace| format!("import type * as {namespace} from \"{specifier}\";")));
+
+    if !imported.named.is_empty() {
+        let named = imported.named.iter().cloned().collect::<Vec<_>>().join(", ");
+        lines.push(format!("import type {{ {named} }} from \"{specifier}\";"));
+    }
+
+    lines
+}
+
+/// The lines `span` covers, if nothing but whitespace and a trailing comment
+/// shares them. Otherwise just `span`
+pub fn whole_lines(text: &str, span: Span) -> Range<usize> {
+    let (start, end) = (span.start as usize, span.end as usize);
+    let line_start = text[..start].rfind('\n').map_or(0, |pos| pos + 1);
+    let line_end = text[end..].find('\n').map_or(text.len(), |pos| end + pos + 1);
+
+    let rest_of_line = text[end..line_end].trim();
+    if text[line_start..start].trim().is_empty() && (rest_of_line.is_empty() || rest_of_line.starts_with("//")) {
+        line_start..line_end
+    } else {
+        start..end
+    }
+}
+
+/// `import { type A, b } from "x"` becomes a type import and a value import
+fn from_declaration(ts_file: &str, declaration: &ImportDeclaration) -> Vec<Import> {
+    let type_only = declaration.import_kind.is_type();
+    let import = |type_only| Import {
+        specifier: declaration.source.value.to_string(),
+        type_only,
+        default: None,
+        namespace: None,
+        named: Vec::new(),
+    };
+    let mut statement_import = import(type_only);
+    let mut inline_types = import(true);
+
+    for specifier in declaration.specifiers.iter().flatten() {
+        use ImportDeclarationSpecifier as IDS;
+        match specifier {
+            IDS::ImportDefaultSpecifier(default) => statement_import.default = Some(default.local.name.to_string()),
+            IDS::ImportNamespaceSpecifier(namespace) => statement_import.namespace = Some(namespace.local.name.to_string()),
+            IDS::ImportSpecifier(named) => {
+                // Keeps the quotes of `{ "a-b" as ab }`
+                let imported = named.imported.span().source_text(ts_file);
+                let local = named.local.name.as_str();
+                let name = if imported == local { local.to_string() } else { format!("{imported} as {local}") };
+
+                if named.import_kind.is_type() && !type_only {
+                    inline_types.named.push(name);
+                } else {
+                    statement_import.named.push(name);
+                }
+            },
+        }
+    }
+
+    // `import { type A } from "x"` only imports a type, not the module itself
+    if inline_types.named.is_empty() {
+        return vec![statement_import];
+    }
+    if statement_import.is_side_effect() {
+        return vec![inline_types];
+    }
+
+    vec![statement_import, inline_types]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn import(specifier: &str, type_only: bool, named: &[&str]) -> Import {
+        Import {
+            specifier: specifier.to_string(),
+            type_only,
+            default: None,
+            namespace: None,
+            named: named.iter().map(|name| name.to_string()).collect(),
+        }
+    }
+
+    #[test]
+    fn split_imports_takes_out_whole_lines() {
+        let ts_file = "import { a } from \"./a.ts\"; // the a\nimport b from \"b\";\n\nconst c = a + b;\n";
+        let (code, imports) = split_imports(ts_file);
+
+        assert_eq!(code, "\nconst c = a + b;\n");
+        assert_eq!(imports, vec![
+            import("./a.ts", false, &["a"]),
+            Import { default: Some("b".to_string()), ..import("b", false, &[]) },
+        ]);
+    }
+
+    #[test]
+    fn split_imports_separates_inline_types() {
+        let (_, imports) = split_imports("import { type A, b as c, \"d-e\" as de } from \"x\";\nimport { type F } from \"x\";\n");
+
+        assert_eq!(imports, vec![
+            import("x", false, &["b as c", "\"d-e\" as de"]),
+            import("x", true, &["A"]),
+            import("x", true, &["F"]),
+        ]);
+    }
+
+    #[test]
+    fn split_imports_ignores_imports_in_strings_and_comments() {
+        let ts_file = "// import { a } from \"a\";\nconst s = `\nimport { b } from \"b\";\n`;\n";
+        assert_eq!(split_imports(ts_file), (ts_file.to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn split_imports_keeps_imports_with_attributes() {
+        let ts_file = "import data from \"./data.json\" with { type: \"json\" };\n";
+        assert_eq!(split_imports(ts_file), (ts_file.to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn split_imports_leaves_broken_files_alone() {
+        let ts_file = "import { a from \"a\";\n";
+        assert_eq!(split_imports(ts_file), (ts_file.to_string(), Vec::new()));
+    }
+
+    #[test]
+    fn remove_imports_keeps_the_others() {
+        let ts_file = "import { a } from \"https://deno.land/x/a.ts\";\nimport { b } from \"./b.ts\";\n";
+        assert_eq!(remove_imports(ts_file, Import::is_url), "import { b } from \"./b.ts\";\n");
+    }
+
+    #[test]
+    fn merge_combines_by_specifier() {
+        let merged = merge(vec![
+            import("./b.ts", false, &["y"]),
+            import("./a.ts", false, &["x", "z"]),
+            import("./b.ts", false, &["x", "y"]),
+            Import { default: Some("def".to_string()), ..import("./a.ts", false, &[]) },
+        ]);
+
+        assert_eq!(merged, vec![
+            "import def, { x, z } from \"./a.ts\";",
+            "import { x, y } from \"./b.ts\";",
+        ]);
+    }
+
+    #[test]
+    fn merge_drops_types_imported_as_values() {
+        let merged = merge(vec![
+            import("x", true, &["A", "B"]),
+            import("x", false, &["A"]),
+            import("y", true, &["C"]),
+        ]);
+
+        assert_eq!(merged, vec![
+            "import { A } from \"x\";",
+            "import type { B } from \"x\";",
+            "import type { C } from \"y\";",
+        ]);
+    }
+
+    #[test]
+    fn merge_keeps_side_effects_only_when_nothing_else_is_imported() {
+        assert_eq!(merge(vec![import("x", false, &[]), import("x", false, &["a"])]), vec!["import { a } from \"x\";"]);
+        assert_eq!(merge(vec![import("x", false, &[]), import("x", false, &[])]), vec!["import \"x\";"]);
+    }
+
+    #[test]
+    fn merge_splits_defaults_and_namespaces() {
+        let merged = merge(vec![
+            Import { default: Some("a".to_string()), ..import("x", false, &[]) },
+            Import { default: Some("b".to_string()), ..import("x", false, &[]) },
+            Import { namespace: Some("ns".to_string()), ..import("x", false, &[]) },
+            Import { default: Some("T".to_string()), ..import("x", true, &["U"]) },
+        ]);
+
+        assert_eq!(merged, vec![
+            "import a from \"x\";",
+            "import b from \"x\";",
+            "import * as ns from \"x\";",
+            "import type T from \"x\";",
+            "import type { U } from \"x\";",
+        ]);
+    }
+}
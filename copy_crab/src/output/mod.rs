@@ -0,0 +1,183 @@
+use std::{fmt::Display, io::{self, Write}, sync::atomic::{AtomicBool, Ordering}};
+
+use crate::models::{Feature, FeatureReport, FileDrift, Modularity, PlannedChange, PresetName, Runtime};
+use crate::settings_finder::{is_host_file, LOCK_FILE};
+
+use colored::Colorize;
+use serde::Serialize;
+
+// Set once in `main`, read everywhere something gets printed
+static JSON: AtomicBool = AtomicBool::new(false);
+// Set once whoever reads the output went away, e.g. `status --json | head -1`
+static CLOSED: AtomicBool = AtomicBool::new(false);
+
+/// Switches every message to one JSON object per line. Colours are turned off
+/// so that no escape codes end up in the emitted strings
+pub fn set_json(enabled: bool) {
+    JSON.store(enabled, Ordering::Relaxed);
+    if enabled {
+        colored::control::set_override(false);
+    }
+}
+
+pub fn is_json() -> bool {
+    JSON.load(Ordering::Relaxed)
+}
+
+#[derive(Debug, Serialize)]
+#[serde(tag = "event", rename_all = "snake_case")]
+pub enum Event {
+    FileWritten { path: String },
+    FileDeleted { path: String },
+    ConfigChanged { path: String, deleted: bool },
+    /// The settings were upgraded to the current schema
+    ConfigMigrated { path: String, from: u64, to: u64, backup: Option<String> },
+    /// A change that `--dry-run` would have made
+    PlannedChange { path: String, action: Action, diff: String },
+    Drift(FileDrift),
+    Status {
+        installation: String,
+        runtime: Runtime,
+        chosen_directory: String,
+        modularity: Modularity,
+        preset: Option<PresetName>,
+        features: Vec<FeatureReport>,
+        extra_files: Vec<String>,
+    },
+    /// Why a feature is part of an installation
+    Why {
+        installation: String,
+        feature: Feature,
+        chosen: bool,
+        needed_by: Vec<Feature>,
+    },
+    /// A feature installed along with the chosen ones, because they need it
+    DependencyAdded {
+        installation: String,
+        feature: Feature,
+        needed_by: Vec<Feature>,
+    },
+    Warning { message: String },
+    Error { message: String },
+}
+
+#[derive(Debug, Clone, Copy, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum Action {
+    Create,
+    Overwrite,
+    Delete,
+}
+
+impl Event {
+    /// The event for a change that has just been applied
+    pub fn applied(change: &PlannedChange) -> Self {
+        let path = change.path.clone();
+        match (is_host_file(&path) || path == LOCK_FILE, &change.after) {
+            (true, after) => Event::ConfigChanged { path, deleted: after.is_none() },
+            (false, Some(_)) => Event::FileWritten { path },
+            (false, None) => Event::FileDeleted { path },
+        }
+    }
+
+    pub fn planned(change: &PlannedChange) -> Self {
+        let action = match (&change.before, &change.after) {
+            (_, None) => Action::Delete,
+            (None, Some(_)) => Action::Create,
+            (Some(_), Some(_)) => Action::Overwrite,
+        };
+
+        Event::PlannedChange { path: change.path.clone(), action, diff: change.diff() }
+    }
+
+    /// What gets printed outside of JSON mode. Writes are silent there,
+    /// like they have always been
+    fn human(&self) -> Option<String> {
+        use Event as E;
+        match self {
+            E::FileWritten { .. } | E::ConfigChanged { .. } => None,
+            E::FileDeleted { path } => Some(format!("Deleted {path}")),
+            E::ConfigMigrated { path, from, to, backup } => Some(match backup {
+                Some(backup) => format!("Upgraded {path} from schema v{from} to v{to}, the old file was saved as {backup}"),
+                None => format!("{path} uses schema v{from} and will be upgraded to v{to} on the next change"),
+            }),
+            E::PlannedChange { diff, .. } => Some(diff.clone()),
+            E::Drift(file) => Some(file.to_string()),
+            E::Status { installation, runtime, chosen_directory, modularity, preset, features, extra_files } => {
+                let mut lines = vec![
+                    format!("{} {}", "Installation:".bold(), installation),
+                    format!("{} {:?}", "Runtime:     ".bold(), runtime),
+                    format!("{} {}", "Directory:   ".bold(), chosen_directory),
+                    format!("{} {:?}", "Modularity:  ".bold(), modularity),
+                ];
+                if let Some(preset_name) = preset {
+                    lines.push(format!("{} {}", "Preset:      ".bold(), preset_name));
+                }
+                lines.push(String::new());
+
+                lines.extend(features.iter().map(|report| report.to_string()));
+                lines.extend(extra_files.iter().map(|path| format!("{:>12}  {path}", "extra".magenta())));
+                Some(lines.join("\n"))
+            },
+            E::Why { installation, feature, chosen, needed_by } => {
+                let name = feature.display_name().bold();
+                let mut lines = Vec::new();
+                if *chosen {
+                    lines.push(format!("{name} was chosen for {installation}"));
+                }
+                if !needed_by.is_empty() {
+                    let dependents: Vec<String> = needed_by.iter().map(Feature::display_name).collect();
+                    lines.push(format!("{name} is needed by {}", dependents.join(", ")));
+                }
+                Some(lines.join("\n"))
+            },
+            E::DependencyAdded { feature, needed_by, .. } => {
+                let dependents: Vec<String> = needed_by.iter().map(Feature::display_name).collect();
+                Some(format!(
+                    "Also installing {}, which {} need{}",
+                    feature.display_name().bold(),
+                    dependents.join(", "),
+                    if dependents.len() == 1 { "s" } else { "" },
+                ))
+            },
+            E::Warning { message } => Some(format!("{} {message}", "Warning:".black().on_yellow())),
+            E::Error { message } => Some(format!("{} {message}", "error:".red().bold())),
+        }
+    }
+}
+
+pub fn emit(event: Event) {
+    if is_json() {
+        // Every field is a plain string or enum, so this can't fail
+        print_line(io::stdout().lock(), serde_json::to_string(&event).unwrap());
+        return;
+    }
+
+    match event {
+        Event::Error { .. } => print_line(io::stderr().lock(), event.human().unwrap_or_default()),
+        _ => if let Some(message) = event.human() {
+            print_line(io::stdout().lock(), message);
+        }
+    }
+}
+
+/// Prints a message meant for people only. Nothing is printed in JSON mode
+pub fn info(message: impl Display) {
+    if !is_json() {
+        print_line(io::stdout().lock(), message);
+    }
+}
+
+/// Unlike `println!`, doesn't panic once the reader closed the pipe. The rest
+/// of the output is dropped then, but the run still finishes what it started
+fn print_line(mut out: impl Write, message: impl Display) {
+    if CLOSED.load(Ordering::Relaxed) {
+        return;
+    }
+
+    if let Err(err) = writeln!(out, "{message}") {
+        if err.kind() == io::ErrorKind::BrokenPipe {
+            CLOSED.store(true, Ordering::Relaxed);
+        }
+    }
+}
@@ -0,0 +1,221 @@
+use std::fs;
+use std::path::Path;
+
+use crate::error::{CopyCrabError, IoContext, Result};
+
+use clap::ValueEnum;
+use serde_json::{Value, json};
+use toml_edit::{ArrayOfTables, DocumentMut, Item, Table};
+
+use super::{jsonc, SETTINGS_KEY};
+
+/// The files the SETTINGS KEY can be stored in. When several of them hold it,
+/// the one declared first wins
+#[derive(Debug, Clone, Copy, PartialEq, ValueEnum)]
+pub enum ConfigHost {
+    /// `copy-paste.json`, shared with other copy-paste tools
+    CopyPasteJson,
+    /// `copy-paste.toml`, for those who prefer TOML
+    CopyPasteToml,
+    /// The `crabSafe` key of `deno.json`
+    DenoJson,
+    /// The `crabSafe` key of `package.json`
+    PackageJson,
+}
+
+impl ConfigHost {
+    pub fn file_name(&self) -> &'static str {
+        use ConfigHost as CH;
+        match self {
+            CH::CopyPasteJson => "copy-paste.json",
+            CH::CopyPasteToml => "copy-paste.toml",
+            CH::DenoJson => "deno.json",
+            CH::PackageJson => "package.json",
+        }
+    }
+
+    /// Files that only exist for copy-paste tools. The other ones belong to the
+    /// project, so copy_crab never creates or deletes them
+    pub fn is_dedicated(&self) -> bool {
+        matches!(self, ConfigHost::CopyPasteJson | ConfigHost::CopyPasteToml)
+    }
+
+    pub fn exists(&self) -> bool {
+        Path::new(self.file_name()).is_file()
+    }
+
+    /// Reads the whole file. JSON files may contain comments
+    pub fn read(&self) -> Result<Value> {
+        let file_contents = self.read_raw()?;
+        match self {
+            ConfigHost::CopyPasteToml => toml_edit::de::from_str(&file_contents)
+                .map_err(|err| self.parse_error(serde::de::Error::custom(err))),
+            _ => serde_json::from_str(&jsonc::strip(&file_contents))
+                .map_err(|source| self.parse_error(source)),
+        }
+    }
+
+    /// The settings stored in this file, if there are any
+    pub fn settings(&self) -> Result<Option<Value>> {
+        if !self.exists() {
+            return Ok(None);
+        }
+
+        Ok(self.read()?.get(SETTINGS_KEY).cloned())
+    }
+
+    /// The file once the SETTINGS KEY holds `settings_value`. Everything else
+    /// in it is left the way it was
+    pub fn with_settings(&self, settings_value: &Value) -> Result<String> {
+        if !self.exists() {
+            if !self.is_dedicated() {
+                return Err(CopyCrabError::Conflict(format!("{} doesn't exist", self.file_name())));
+            }
+
+            return match self {
+                ConfigHost::CopyPasteToml => {
+                    let mut document = DocumentMut::new();
+                    document.insert(SETTINGS_KEY, Item::Table(to_toml(settings_value)?));
+                    Ok(document.to_string())
+                },
+                _ => serde_json::to_string_pretty(&json!({ SETTINGS_KEY: settings_value }))
+                    .map_err(|source| self.parse_error(source)),
+            };
+        }
+
+        let file_contents = self.read_raw()?;
+        match self {
+            ConfigHost::CopyPasteToml => {
+                let mut document = self.parse_toml(&file_contents)?;
+                document.insert(SETTINGS_KEY, Item::Table(to_toml(settings_value)?));
+                Ok(document.to_string())
+            },
+            _ => jsonc::set_key(&file_contents, SETTINGS_KEY, settings_value)
+                .ok_or_else(|| self.not_an_object()),
+        }
+    }
+
+    /// The file without the SETTINGS KEY, or `None` if nothing would be left
+    /// in a file that only exists for copy-paste tools
+    pub fn without_settings(&self) -> Result<Option<String>> {
+        let file_contents = self.read_raw()?;
+        if let ConfigHost::CopyPasteToml = self {
+            let mut document = self.parse_toml(&file_contents)?;
+            document.remove(SETTINGS_KEY);
+            return Ok((!document.is_empty()).then(|| document.to_string()));
+        }
+
+        let mut file_value = self.read()?;
+        let Some(file_object) = file_value.as_object_mut() else {
+            return Err(self.not_an_object());
+        };
+        file_object.remove(SETTINGS_KEY);
+
+        if file_object.is_empty() && self.is_dedicated() {
+            // Nobody else is using it, so it's just taking up extra space
+            return Ok(None);
+        }
+
+        // Somebody else is using it, so you can just delete your own part
+        jsonc::remove_key(&file_contents, SETTINGS_KEY)
+            .map(Some)
+            .ok_or_else(|| self.not_an_object())
+    }
+
+    /// Whether the SETTINGS KEY shows up anywhere in the file, which is all
+    /// that can be told about a file that doesn't parse
+    fn mentions_settings(&self) -> bool {
+        self.read_raw().is_ok_and(|file_contents| file_contents.contains(&format!("\"{SETTINGS_KEY}\"")))
+    }
+
+    pub fn parse_error(&self, source: serde_json::Error) -> CopyCrabError {
+        CopyCrabError::ConfigParse { path: self.file_name().to_string(), source }
+    }
+
+    fn read_raw(&self) -> Result<String> {
+        fs::read_to_string(self.file_name()).at(self.file_name())
+    }
+
+    fn parse_toml(&self, file_contents: &str) -> Result<DocumentMut> {
+        file_contents
+            .parse::<DocumentMut>()
+            .map_err(|err| self.parse_error(serde::de::Error::custom(err)))
+    }
+
+    fn not_an_object(&self) -> CopyCrabError {
+        CopyCrabError::Conflict(format!("{} doesn't contain a JSON object", self.file_name()))
+    }
+}
+
+/// Where the settings were found, along with the project files that were
+/// skipped because they couldn't be read
+pub struct Located {
+    pub found: Option<(ConfigHost, Value)>,
+    pub skipped: Vec<CopyCrabError>,
+}
+
+/// Looks for the SETTINGS KEY in order of precedence, stopping at the first
+/// file that has it
+pub fn search() -> Result<Located> {
+    let mut skipped = Vec::new();
+    for host in ConfigHost::value_variants() {
+        match host.settings() {
+            Ok(Some(settings_value)) => return Ok(Located { found: Some((*host, settings_value)), skipped }),
+            Ok(None) => {},
+            // A broken file of the project is none of our business, unless it holds the settings
+            Err(err) if !host.is_dedicated() && !host.mentions_settings() => skipped.push(err),
+            Err(err) => return Err(err),
+        }
+    }
+
+    Ok(Located { found: None, skipped })
+}
+
+/// The file the settings are read from and saved to
+pub fn locate() -> Result<Option<(ConfigHost, Value)>> {
+    Ok(search()?.found)
+}
+
+/// The files after `host` that hold the SETTINGS KEY as well, and are ignored.
+/// Files that can't be read are left out, they're ignored either way
+pub fn shadowed_by(host: ConfigHost) -> Vec<ConfigHost> {
+    ConfigHost::value_variants()
+        .iter()
+        .skip_while(|other| **other != host)
+        .skip(1)
+        .filter(|other| other.settings().is_ok_and(|settings_value| settings_value.is_some()))
+        .copied()
+        .collect()
+}
+
+/// Whether `path` is one of the files the settings can be stored in
+pub fn is_host_file(path: &str) -> bool {
+    ConfigHost::value_variants().iter().any(|host| host.file_name() == path)
+}
+
+/// Turns the settings into a TOML table. Lists of objects, like the
+/// installations, become arrays of tables so they stay readable
+fn to_toml(settings_value: &Value) -> Result<Table> {
+    let host = ConfigHost::CopyPasteToml;
+    let document = toml_edit::ser::to_document(settings_value)
+        .map_err(|err| host.parse_error(serde::ser::Error::custom(err)))?;
+
+    let mut table = document.as_table().clone();
+    for (_, item) in table.iter_mut() {
+        let Some(array) = item.as_array() else {
+            continue;
+        };
+        if array.is_empty() || !array.iter().all(|value| value.is_inline_table()) {
+            continue;
+        }
+
+        let tables: ArrayOfTables = array
+            .iter()
+            .filter_map(|value| value.as_inline_table())
+            .map(|inline_table| inline_table.clone().into_table())
+            .collect();
+        *item = Item::ArrayOfTables(tables);
+    }
+
+    Ok(table)
+}
@@ -0,0 +1,415 @@
+//! Just enough of a JSONC scanner to change one key of the top-level object,
+//! while every other byte of the file stays the way it was written
+
+use std::ops::Range;
+
+use serde::Serialize;
+use serde_json::{ser::PrettyFormatter, Serializer, Value};
+
+/// A `"key": value` pair of the top-level object
+struct Member {
+    key: String,
+    /// Where the opening quote of the key is
+    start: usize,
+    value: Range<usize>,
+}
+
+/// The top-level object of a file, found in its stripped version
+struct TopLevel {
+    open: usize,
+    members: Vec<Member>,
+}
+
+/// Replaces comments and trailing commas with spaces, which turns JSONC into
+/// JSON. Byte offsets stay the same, so positions found in the result can be
+/// used on the original text
+pub fn strip(text: &str) -> String {
+    blank_comments(text, true)
+}
+
+/// `strip`, optionally keeping trailing commas, which the edits have to see
+fn blank_comments(text: &str, trailing_commas: bool) -> String {
+    let bytes = text.as_bytes();
+    let mut stripped = bytes.to_vec();
+    let mut last_comma = None;
+    let mut i = 0;
+
+    while i < bytes.len() {
+        match (bytes[i], bytes.get(i + 1)) {
+            (b'"', _) => {
+                last_comma = None;
+                i = skip_string(bytes, i);
+                continue;
+            },
+            (b'/', Some(b'/')) => {
+                let end = bytes[i..].iter().position(|&byte| byte == b'\n').map_or(bytes.len(), |pos| i + pos);
+                blank(&mut stripped, i..end);
+                i = end;
+                continue;
+            },
+            (b'/', Some(b'*')) => {
+                let end = bytes[i + 2..]
+                    .windows(2)
+                    .position(|pair| pair == b"*/")
+                    .map_or(bytes.len(), |pos| i + 2 + pos + 2);
+                blank(&mut stripped, i..end);
+                i = end;
+                continue;
+            },
+            (b',', _) => last_comma = Some(i),
+            (b'}' | b']', _) => if let Some(comma) = last_comma.take().filter(|_| trailing_commas) {
+                stripped[comma] = b' ';
+            },
+            (byte, _) if byte.is_ascii_whitespace() => {},
+            _ => last_comma = None,
+        }
+        i += 1;
+    }
+
+    // Only whole comments were replaced, so this is still valid UTF-8
+    String::from_utf8(stripped).unwrap_or_else(|_| text.to_string())
+}
+
+/// `text` with `key` of the top-level object set to `value`. The key is added
+/// at the end if it isn't there yet. `None` if there is no top-level object
+pub fn set_key(text: &str, key: &str, value: &Value) -> Option<String> {
+    let stripped = blank_comments(text, false);
+    let top_level = scan(&stripped)?;
+
+    let mut fin_str = text.to_string();
+    match top_level.members.iter().find(|member| member.key == key) {
+        Some(member) => {
+            let rendered = match line_indent(text, member.start) {
+                Some(indent) => render(value, indent),
+                None => value.to_string(),
+            };
+            fin_str.replace_range(member.value.clone(), &rendered);
+        },
+        None => {
+            let key = serde_json::to_string(key).ok()?;
+            let bytes = stripped.as_bytes();
+
+            // Minified files stay minified
+            let Some(indent) = top_level.members.last().map_or(Some(""), |last| line_indent(text, last.start)) else {
+                let last = top_level.members.last()?;
+                fin_str.insert_str(last.value.end, &format!(",{key}:{value}"));
+                return Some(fin_str);
+            };
+            let indent = if indent.is_empty() { "  " } else { indent };
+            let member = format!("\n{indent}{key}: {}", render(value, indent));
+
+            match top_level.members.last() {
+                Some(last) => {
+                    // Goes after the comments at the end of the last member's line
+                    let mut after = skip_inline_whitespace(bytes, last.value.end);
+                    let has_comma = bytes.get(after) == Some(&b',');
+                    if has_comma {
+                        after = skip_inline_whitespace(bytes, after + 1);
+                    }
+                    let insert_at = match bytes.get(after) {
+                        Some(b'\n' | b'\r') => after,
+                        _ => last.value.end + usize::from(has_comma),
+                    };
+
+                    // A trailing comma stays trailing
+                    let member = if has_comma { format!("{member},") } else { member };
+                    fin_str.insert_str(insert_at, &member);
+                    if !has_comma {
+                        fin_str.insert(last.value.end, ',');
+                    }
+                },
+                None => fin_str.insert_str(top_level.open + 1, &format!("{member}\n")),
+            }
+        },
+    }
+
+    Some(fin_str)
+}
+
+/// `text` without `key` in the top-level object, along with the comma that
+/// separated it. `None` if there is no top-level object
+pub fn remove_key(text: &str, key: &str) -> Option<String> {
+    let stripped = blank_comments(text, false);
+    let top_level = scan(&stripped)?;
+    let bytes = stripped.as_bytes();
+
+    let Some(index) = top_level.members.iter().position(|member| member.key == key) else {
+        return Some(text.to_string());
+    };
+    let member = &top_level.members[index];
+
+    // Take the whole line if the member has it to itself
+    let line_start = text[..member.start].rfind('\n').map_or(0, |pos| pos + 1);
+    let owns_line = text[line_start..member.start].trim().is_empty();
+    let start = if owns_line { line_start } else { member.start };
+
+    let mut end = member.value.end;
+    let next_comma = skip_whitespace(bytes, end);
+    let has_next_comma = bytes.get(next_comma) == Some(&b',');
+    if has_next_comma {
+        end = next_comma + 1;
+    }
+    if owns_line {
+        let rest_of_line = bytes[end..].iter().position(|&byte| byte == b'\n');
+        if let Some(pos) = rest_of_line.filter(|&pos| stripped[end..end + pos].trim().is_empty()) {
+            end += pos + 1;
+        }
+    } else if has_next_comma {
+        end = skip_inline_whitespace(bytes, end);
+    }
+
+    let mut fin_str = text.to_string();
+    fin_str.replace_range(start..end, "");
+
+    // The last member leaves the comma of the one before it behind
+    if !has_next_comma && index > 0 {
+        let previous = &top_level.members[index - 1];
+        let comma = skip_whitespace(bytes, previous.value.end);
+        if bytes.get(comma) == Some(&b',') {
+            // On the same line, the space after the comma goes as well
+            let comma_end = if owns_line { comma + 1 } else { start };
+            fin_str.replace_range(comma..comma_end, "");
+        }
+    }
+
+    Some(fin_str)
+}
+
+/// Finds the members of the top-level object in a stripped file
+fn scan(stripped: &str) -> Option<TopLevel> {
+    let bytes = stripped.as_bytes();
+    let open = skip_whitespace(bytes, 0);
+    if bytes.get(open) != Some(&b'{') {
+        return None;
+    }
+
+    let mut members = Vec::new();
+    let mut i = skip_whitespace(bytes, open + 1);
+    while bytes.get(i) == Some(&b'"') {
+        let start = i;
+        i = skip_string(bytes, i);
+        let key = serde_json::from_str(&stripped[start..i]).ok()?;
+
+        i = skip_whitespace(bytes, i);
+        if bytes.get(i) != Some(&b':') {
+            return None;
+        }
+        let value_start = skip_whitespace(bytes, i + 1);
+        let value_end = skip_value(bytes, value_start);
+        members.push(Member { key, start, value: value_start..value_end });
+
+        i = skip_whitespace(bytes, value_end);
+        if bytes.get(i) == Some(&b',') {
+            i = skip_whitespace(bytes, i + 1);
+        }
+    }
+
+    Some(TopLevel { open, members })
+}
+
+/// Pretty-prints `value` so that it lines up with a key indented by `indent`
+fn render(value: &Value, indent: &str) -> String {
+    let unit = if indent.is_empty() { "  " } else { indent };
+    let mut rendered = Vec::new();
+    let mut serializer = Serializer::with_formatter(&mut rendered, PrettyFormatter::with_indent(unit.as_bytes()));
+
+    // A `Value` always serializes
+    let _ = value.serialize(&mut serializer);
+    String::from_utf8_lossy(&rendered).replace('\n', &format!("\n{indent}"))
+}
+
+/// The whitespace in front of whatever is at `pos`, or `None` if something
+/// else comes before it on its line
+fn line_indent(text: &str, pos: usize) -> Option<&str> {
+    let line_start = text[..pos].rfind('\n').map_or(0, |newline| newline + 1);
+    let indent = &text[line_start..pos];
+    indent.trim().is_empty().then_some(indent)
+}
+
+fn blank(stripped: &mut [u8], range: Range<usize>) {
+    for byte in &mut stripped[range] {
+        if *byte != b'\n' {
+            *byte = b' ';
+        }
+    }
+}
+
+/// Returns the position right after the string starting at `start`
+fn skip_string(bytes: &[u8], start: usize) -> usize {
+    let mut i = start + 1;
+    while i < bytes.len() {
+        match bytes[i] {
+            b'\\' => i += 2,
+            b'"' => return i + 1,
+            _ => i += 1,
+        }
+    }
+
+    bytes.len()
+}
+
+/// Like `skip_whitespace`, but stays on the same line
+fn skip_inline_whitespace(bytes: &[u8], start: usize) -> usize {
+    bytes[start.min(bytes.len())..]
+        .iter()
+        .position(|byte| !matches!(byte, b' ' | b'\t'))
+        .map_or(bytes.len(), |pos| start + pos)
+}
+
+fn skip_whitespace(bytes: &[u8], start: usize) -> usize {
+    bytes[start.min(bytes.len())..]
+        .iter()
+        .position(|byte| !byte.is_ascii_whitespace())
+        .map_or(bytes.len(), |pos| start + pos)
+}
+
+/// Returns the position right after the value starting at `start`
+fn skip_value(bytes: &[u8], start: usize) -> usize {
+    match bytes.get(start) {
+        Some(b'"') => skip_string(bytes, start),
+        Some(b'{' | b'[') => {
+            let mut depth = 0;
+            let mut i = start;
+            while i < bytes.len() {
+                match bytes[i] {
+                    b'"' => {
+                        i = skip_string(bytes, i);
+                        continue;
+                    },
+                    b'{' | b'[' => depth += 1,
+                    b'}' | b']' => {
+                        depth -= 1;
+                        if depth == 0 {
+                            return i + 1;
+                        }
+                    },
+                    _ => {},
+                }
+                i += 1;
+            }
+
+            bytes.len()
+        },
+        _ => bytes[start..]
+            .iter()
+            .position(|byte| matches!(byte, b',' | b'}' | b']') || byte.is_ascii_whitespace())
+            .map_or(bytes.len(), |pos| start + pos),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn settings() -> Value {
+        json!({ "runtime": "Deno" })
+    }
+
+    #[test]
+    fn strip_blanks_comments_and_trailing_commas() {
+        let text = "{\n  // a comment\n  \"a\": \"// not a comment\", /* b */\n  \"c\": [1, 2,],\n}";
+        let stripped = strip(text);
+
+        assert_eq!(stripped.len(), text.len());
+        assert_eq!(
+            serde_json::from_str::<Value>(&stripped).unwrap(),
+            json!({ "a": "// not a comment", "c": [1, 2] })
+        );
+    }
+
+    #[test]
+    fn set_key_on_minified() {
+        assert_eq!(
+            set_key(r#"{"other":1}"#, "crabSafe", &settings()).unwrap(),
+            r#"{"other":1,"crabSafe":{"runtime":"Deno"}}"#
+        );
+        assert_eq!(
+            set_key(r#"{"crabSafe":{},"other":1}"#, "crabSafe", &settings()).unwrap(),
+            r#"{"crabSafe":{"runtime":"Deno"},"other":1}"#
+        );
+    }
+
+    #[test]
+    fn set_key_replaces_only_the_value() {
+        assert_eq!(
+            set_key("{\n  // mine\n  \"crabSafe\": {}, // still mine\n  \"other\": 1\n}", "crabSafe", &settings()).unwrap(),
+            "{\n  // mine\n  \"crabSafe\": {\n    \"runtime\": \"Deno\"\n  }, // still mine\n  \"other\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn remove_key_on_minified() {
+        assert_eq!(remove_key(r#"{"crabSafe":{},"other":1}"#, "crabSafe").unwrap(), r#"{"other":1}"#);
+        assert_eq!(remove_key(r#"{"other":1,"crabSafe":{}}"#, "crabSafe").unwrap(), r#"{"other":1}"#);
+        assert_eq!(remove_key(r#"{"a":1,"crabSafe":{},"b":2}"#, "crabSafe").unwrap(), r#"{"a":1,"b":2}"#);
+    }
+
+    #[test]
+    fn set_key_keeps_tab_indentation() {
+        assert_eq!(
+            set_key("{\n\t\"other\": 1\n}", "crabSafe", &settings()).unwrap(),
+            "{\n\t\"other\": 1,\n\t\"crabSafe\": {\n\t\t\"runtime\": \"Deno\"\n\t}\n}"
+        );
+    }
+
+    #[test]
+    fn remove_key_with_tab_indentation() {
+        assert_eq!(
+            remove_key("{\n\t\"other\": 1,\n\t\"crabSafe\": {\n\t\t\"runtime\": \"Deno\"\n\t}\n}", "crabSafe").unwrap(),
+            "{\n\t\"other\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn set_key_keeps_trailing_comma() {
+        assert_eq!(
+            set_key("{\n  \"other\": 1,\n}", "crabSafe", &settings()).unwrap(),
+            "{\n  \"other\": 1,\n  \"crabSafe\": {\n    \"runtime\": \"Deno\"\n  },\n}"
+        );
+    }
+
+    #[test]
+    fn remove_key_with_trailing_comma() {
+        assert_eq!(
+            remove_key("{\n  \"other\": 1,\n  \"crabSafe\": {},\n}", "crabSafe").unwrap(),
+            "{\n  \"other\": 1,\n}"
+        );
+    }
+
+    #[test]
+    fn set_key_after_comment_on_last_member() {
+        assert_eq!(
+            set_key("{\n  \"other\": 1 // keep me\n}", "crabSafe", &settings()).unwrap(),
+            "{\n  \"other\": 1, // keep me\n  \"crabSafe\": {\n    \"runtime\": \"Deno\"\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn remove_key_before_comment() {
+        assert_eq!(
+            remove_key("{\n  \"other\": 1,\n  \"crabSafe\": {} // mine\n}", "crabSafe").unwrap(),
+            "{\n  \"other\": 1\n}"
+        );
+    }
+
+    #[test]
+    fn set_key_on_empty_object() {
+        assert_eq!(
+            set_key("{}", "crabSafe", &settings()).unwrap(),
+            "{\n  \"crabSafe\": {\n    \"runtime\": \"Deno\"\n  }\n}"
+        );
+    }
+
+    #[test]
+    fn remove_missing_key_changes_nothing() {
+        assert_eq!(remove_key("{}", "crabSafe").unwrap(), "{}");
+        assert_eq!(remove_key("{ /* a */ \"other\": 1 }", "crabSafe").unwrap(), "{ /* a */ \"other\": 1 }");
+    }
+
+    #[test]
+    fn no_top_level_object() {
+        assert!(set_key("[1, 2]", "crabSafe", &settings()).is_none());
+        assert!(remove_key("\"text\"", "crabSafe").is_none());
+    }
+}
@@ -0,0 +1,99 @@
+use std::collections::BTreeMap;
+use std::fs;
+
+use crate::error::{CopyCrabError, IoContext, Result};
+use crate::models::{Feature, PlannedChange, ProjectChoices};
+
+use serde::{Serialize, Deserialize};
+use sha2::{Digest, Sha256};
+
+pub const LOCK_FILE: &str = "copy-paste.lock";
+
+/// What copy_crab wrote the last time, so later runs can tell a file the user
+/// edited apart from one that copy_crab now ships differently
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Lock {
+    /// The copy_crab version that last wrote the lockfile
+    pub version: String,
+    pub installations: BTreeMap<String, LockedInstallation>,
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct LockedInstallation {
+    /// Hash of the implementation embedded in copy_crab, per feature
+    pub features: BTreeMap<Feature, String>,
+    /// Hash of every file as it was written
+    pub files: BTreeMap<String, String>,
+}
+
+impl LockedInstallation {
+    pub fn new(choices: &ProjectChoices) -> Result<Self> {
+        let features = choices.installed_features()
+            .into_iter()
+            .map(|feature| {
+                let source_hash = hash(feature.get_implementation());
+                (feature, source_hash)
+            })
+            .collect();
+
+        let files = choices.expected_files()?
+            .into_iter()
+            .map(|file| (file.path, hash(&file.contents)))
+            .collect();
+
+        Ok(Self { features, files })
+    }
+}
+
+pub fn hash(contents: &str) -> String {
+    Sha256::digest(contents.as_bytes())
+        .iter()
+        .map(|byte| format!("{byte:02x}"))
+        .collect()
+}
+
+/// What was recorded for the installation called `name`. A missing or broken
+/// lockfile just means nothing is known about the files
+pub fn locked_installation(name: &str) -> Option<LockedInstallation> {
+    read_lock().ok()?.installations.remove(name)
+}
+
+/// The lockfile once the files of `choices` have been written
+pub fn plan_lock(choices: &ProjectChoices) -> Result<PlannedChange> {
+    let mut lock = read_lock().unwrap_or_default();
+    lock.installations.insert(choices.name.clone(), LockedInstallation::new(choices)?);
+    plan_write(lock)
+}
+
+/// The lockfile once the installation called `name` is gone. It is deleted
+/// together with the last installation
+pub fn plan_lock_removal(name: &str) -> Result<PlannedChange> {
+    let mut lock = read_lock().unwrap_or_default();
+    lock.installations.remove(name);
+
+    if lock.installations.is_empty() {
+        return Ok(PlannedChange::delete(LOCK_FILE.to_string()));
+    }
+    plan_write(lock)
+}
+
+fn plan_write(mut lock: Lock) -> Result<PlannedChange> {
+    let before = read_lock().ok();
+
+    // Only claim the lockfile for this version if something in it changes
+    let unchanged = before.as_ref().is_some_and(|before| before.installations == lock.installations);
+    lock.version = match before {
+        Some(before) if unchanged => before.version,
+        _ => env!("CARGO_PKG_VERSION").to_string(),
+    };
+
+    let fin_str = serde_json::to_string_pretty(&lock)
+        .map_err(|source| CopyCrabError::ConfigParse { path: LOCK_FILE.to_string(), source })?;
+    Ok(PlannedChange::write(LOCK_FILE.to_string(), fin_str))
+}
+
+fn read_lock() -> Result<Lock> {
+    let file_contents = fs::read_to_string(LOCK_FILE).at(LOCK_FILE)?;
+    serde_json::from_str(&file_contents)
+        .map_err(|source| CopyCrabError::ConfigParse { path: LOCK_FILE.to_string(), source })
+}
@@ -0,0 +1,152 @@
+use serde_json::{Map, Value};
+
+use crate::error::{CopyCrabError, Result};
+use crate::models::DEFAULT_INSTALLATION;
+
+use super::{project_root, SETTINGS_KEY};
+
+/// Bump this together with a new entry in `MIGRATIONS` whenever a change to
+/// the models changes what gets saved
+pub const SCHEMA_VERSION: u64 = 3;
+pub const VERSION_KEY: &str = "schemaVersion";
+
+type Migration = fn(&mut Map<String, Value>);
+
+/// `MIGRATIONS[n]` upgrades the settings from version `n` to `n + 1`
+const MIGRATIONS: [Migration; SCHEMA_VERSION as usize] = [
+    // v0 is the unversioned format, which only lacked the version field
+    |_| {},
+    // v1 stored the directory the way it was chosen, often as an absolute path
+    |settings| {
+        if let Some(Value::String(dir)) = settings.get_mut("chosen_directory") {
+            *dir = project_root::relative(dir);
+        }
+    },
+    // v2 held a single installation
+    |settings| {
+        let mut installation = std::mem::take(settings);
+        installation.remove(VERSION_KEY);
+        installation.insert("name".to_string(), DEFAULT_INSTALLATION.into());
+        settings.insert("installations".to_string(), Value::Array(vec![Value::Object(installation)]));
+    },
+];
+
+/// Upgrades `settings` to the current schema. Returns the version it was
+/// upgraded from, or `None` if it already was up to date
+pub fn migrate(settings: &mut Value, path: &str) -> Result<Option<u64>> {
+    let found = stored_version(settings, path)?;
+    let Some(settings) = settings.as_object_mut() else {
+        return Err(invalid_settings(path, "expected an object"));
+    };
+
+    if found > SCHEMA_VERSION {
+        return Err(CopyCrabError::UnsupportedSchema { path: path.to_string(), found });
+    }
+    if found == SCHEMA_VERSION {
+        return Ok(None);
+    }
+
+    for migration in &MIGRATIONS[found as usize..] {
+        migration(settings);
+    }
+    settings.insert(VERSION_KEY.to_string(), SCHEMA_VERSION.into());
+
+    Ok(Some(found))
+}
+
+/// The schema version `settings` were saved with
+pub fn stored_version(settings: &Value, path: &str) -> Result<u64> {
+    let Some(settings) = settings.as_object() else {
+        return Err(invalid_settings(path, "expected an object"));
+    };
+
+    match settings.get(VERSION_KEY) {
+        // Written before the schema was versioned
+        None => Ok(0),
+        Some(version) => version
+            .as_u64()
+            .ok_or_else(|| invalid_settings(path, &format!("{VERSION_KEY} must be a positive number"))),
+    }
+}
+
+fn invalid_settings(path: &str, reason: &str) -> CopyCrabError {
+    CopyCrabError::InvalidSettings {
+        key: SETTINGS_KEY.to_string(),
+        path: path.to_string(),
+        source: serde::de::Error::custom(reason),
+    }
+}
+
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    fn migrated(mut settings: Value) -> (Option<u64>, Value) {
+        let from = migrate(&mut settings, "copy-paste.json").unwrap();
+        (from, settings)
+    }
+
+    fn installation(chosen_directory: &str) -> Value {
+        json!({
+            "runtime": "Deno",
+            "chosen_directory": chosen_directory,
+            "modularity": "SingleFile",
+            "feature_set": { "Custom": { "features": ["Core"] } },
+        })
+    }
+
+    fn current(chosen_directory: &str) -> Value {
+        let mut installation = installation(chosen_directory);
+        installation["name"] = json!(DEFAULT_INSTALLATION);
+        json!({ "installations": [installation], VERSION_KEY: SCHEMA_VERSION })
+    }
+
+    #[test]
+    fn migrates_v0() {
+        assert_eq!(migrated(installation("src/lib")), (Some(0), current("src/lib")));
+    }
+
+    #[test]
+    fn migrates_v1_with_an_absolute_path() {
+        // Tests run from the crate directory, which stands in for the project root
+        let absolute = std::env::current_dir().unwrap().join("src").join("lib");
+        let mut settings = installation(&absolute.display().to_string());
+        settings[VERSION_KEY] = json!(1);
+
+        assert_eq!(migrated(settings), (Some(1), current("src/lib")));
+    }
+
+    #[test]
+    fn migrates_v2() {
+        let mut settings = installation("src/lib");
+        settings[VERSION_KEY] = json!(2);
+
+        assert_eq!(migrated(settings), (Some(2), current("src/lib")));
+    }
+
+    #[test]
+    fn current_version_is_left_alone() {
+        assert_eq!(migrated(current("src/lib")), (None, current("src/lib")));
+    }
+
+    #[test]
+    fn newer_versions_are_refused() {
+        let mut settings = current("src/lib");
+        settings[VERSION_KEY] = json!(SCHEMA_VERSION + 1);
+
+        let Err(CopyCrabError::UnsupportedSchema { found, .. }) = migrate(&mut settings, "copy-paste.json") else {
+            panic!("expected an unsupported schema");
+        };
+        assert_eq!(found, SCHEMA_VERSION + 1);
+    }
+
+    #[test]
+    fn version_has_to_be_a_number() {
+        let mut settings = installation("src/lib");
+        settings[VERSION_KEY] = json!("3");
+
+        assert!(matches!(migrate(&mut settings, "copy-paste.json"), Err(CopyCrabError::InvalidSettings { .. })));
+    }
+}
@@ -1,83 +1,146 @@
+mod migrations;
+mod jsonc;
+mod hosts;
+mod project_lock;
+mod project_root;
+mod lockfile;
+mod user_defaults;
+
+use std::collections::BTreeMap;
 use std::fs;
+use std::path::Path;
+use std::sync::OnceLock;
+
+pub use migrations::SCHEMA_VERSION;
+pub use hosts::{ConfigHost, is_host_file};
+pub use project_lock::acquire as lock_project;
+pub use user_defaults::{UserDefaults, load as user_defaults, path as user_defaults_path};
+pub use lockfile::{LOCK_FILE, LockedInstallation, hash, locked_installation, plan_lock};
+pub use project_root::{enter as enter_project_root, resolve as resolve_path, relative as relative_to_root};
 
-use crate::models::{ProjectChoices, Modularity};
+use crate::error::{CopyCrabError, IoContext, Result};
+use crate::models::{Feature, ProjectChoices, Modularity, PlannedChange, FileState, Settings, write_atomically};
+use crate::output::{self, Event};
 
-use anyhow::bail;
 use colored::Colorize;
 use serde_json::{Value, json};
 
-const FILE_NAME: &'static str = "copy-paste.json";
-const SETTINGS_KEY: &'static str = "crabSafe";
+const SETTINGS_KEY: &str = "crabSafe";
 
-pub fn find_settings() -> anyhow::Result<Option<ProjectChoices>> {
-    // Check if the file exists
-    if let Err(_) = fs::metadata(FILE_NAME) {
-        println!(
-            "{}. A file will be created after choosing your settings",
-            format!("File {} doesn't exist", FILE_NAME.cyan()).bold()
-        );
-        return Ok(None);
-    }
+// Read once, the first time a custom preset is looked up
+static CUSTOM_PRESETS: OnceLock<BTreeMap<String, Vec<Feature>>> = OnceLock::new();
 
-    let found_config = fs::read_to_string(FILE_NAME)?;
-    let found_config: Value = serde_json::from_str(&found_config)?;
+pub fn find_settings() -> Result<Option<Settings>> {
+    let located = hosts::search()?;
+    for err in located.skipped {
+        output::emit(Event::Warning {
+            message: format!("{err}. It wasn't searched for {SETTINGS_KEY}"),
+        });
+    }
 
+    let Some((host, settings_value)) = located.found else {
+        let default_host = ConfigHost::CopyPasteJson;
+        if default_host.exists() {
+            // This just means that another person is using "copy-paste.json"
+            output::info(format!(
+                "{} not found in {}. An entry will be created after choosing your settings.",
+                SETTINGS_KEY.blue().bold(),
+                default_host.file_name().blue().bold(),
+            ));
+        } else {
+            let root = std::env::current_dir().at(".")?;
+            output::info(format!(
+                "{}. A file will be created after choosing your settings",
+                format!("File {} doesn't exist in {}", default_host.file_name().cyan(), root.display()).bold()
+            ));
+        }
 
-    match found_config.get(SETTINGS_KEY) {
-        Some(settings_value) => {
-            let found_config = serde_json::from_value::<ProjectChoices>(settings_value.clone());
+        return Ok(None);
+    };
 
-            // Check if someone else is using copy-paste json as well
-            if let Err(_) = found_config {
-                // Can't use the `?` operator, since this line is mandatory
-                let message = format!(
-                    "A key of {SETTINGS_KEY} was found in {FILE_NAME}, {}. {} {}",
-                    "but it contained invalid configuration settings.",
-                    "Please delete the key-value pair",
-                    "if you want to import this library from this tool"
-                );
+    for ignored_host in hosts::shadowed_by(host) {
+        output::emit(Event::Warning {
+            message: format!(
+                "{SETTINGS_KEY} is also set in {}, but only the settings in {} are used",
+                ignored_host.file_name(),
+                host.file_name()
+            ),
+        });
+    }
 
-                bail!(message.truecolor(200, 0, 0).italic());
-            }
+    let (found_settings, migrated_from) = parse_settings(settings_value, host)?;
+    if let Some(from) = migrated_from {
+        // Only upgraded in memory, the file follows with the next change
+        output::emit(Event::ConfigMigrated {
+            path: host.file_name().to_string(),
+            from,
+            to: SCHEMA_VERSION,
+            backup: None,
+        });
+    }
 
-            Ok(Some(found_config.unwrap()))
-        },
+    found_settings.check_presets()?;
+    Ok(Some(found_settings))
+}
 
-        // This just means that another person is using "copy-paste.json"
-        None => {
-            println!(
-                "{} not found in {}. {}",
-                SETTINGS_KEY.blue().bold(),
-                FILE_NAME.blue().bold(),
-                "An entry will be created after choosing your settings."
-            );
+/// Reads the stored settings without printing anything. Used by shell
+/// completions, where any problem just means there's nothing to offer
+pub fn peek_settings() -> Option<Settings> {
+    let (host, settings_value) = hosts::locate().ok()??;
+    parse_settings(settings_value, host).ok().map(|(settings, _)| settings)
+}
 
-            Ok(None)
+/// Presets from the user-level config, overridden by the ones defined in the
+/// project. Problems with either file are reported when they are loaded for real
+pub fn custom_presets() -> &'static BTreeMap<String, Vec<Feature>> {
+    CUSTOM_PRESETS.get_or_init(|| {
+        let mut presets = user_defaults::load()
+            .map(|defaults| defaults.presets)
+            .unwrap_or_default();
+        if let Some(settings) = peek_settings() {
+            presets.extend(settings.presets);
         }
-    }
+
+        presets
+    })
 }
 
-pub fn save_settings(choices: &ProjectChoices) -> anyhow::Result<()> {
-    // Check if the file exists to begin with
-    let fin_str = if let Err(_) = fs::metadata(FILE_NAME) {
-        // Create a new file since it doesn't exist
-        serde_json::to_string_pretty(&json!({
-            SETTINGS_KEY: choices
-        }))?
-    } else {
-        // Append to the file
-        let file_contents = fs::read_to_string(FILE_NAME)?;
-        let mut file_contents: Value = serde_json::from_str(&file_contents)?;
-        file_contents[SETTINGS_KEY] = serde_json::to_value(&choices)?;
-        serde_json::to_string_pretty(&file_contents)?
+/// What the settings file looks like once `choices` are saved into it.
+/// Other installations are kept as they are
+pub fn plan_save(choices: &ProjectChoices) -> Result<PlannedChange> {
+    let (host, mut settings) = match hosts::locate()? {
+        Some((host, settings_value)) => (host, parse_settings(settings_value, host)?.0),
+        // A new entry goes into "copy-paste.json", which is created if needed
+        None => (ConfigHost::CopyPasteJson, Settings::default()),
     };
+    settings.upsert(choices);
 
-    fs::write(FILE_NAME, fin_str)?;
+    // Everything outside of the SETTINGS KEY is left untouched
+    let fin_str = host.with_settings(&settings_to_value(&settings, host)?)?;
+    Ok(PlannedChange::write(host.file_name().to_string(), fin_str))
+}
 
-    Ok(())
+/// Moves the settings into the file `to`, upgrading them on the way
+pub fn plan_move(to: ConfigHost) -> Result<Vec<PlannedChange>> {
+    let (from, settings_value) = hosts::locate()?.ok_or(CopyCrabError::NotInstalled)?;
+    if from == to {
+        return Err(CopyCrabError::Conflict(format!("The settings are already stored in {}", to.file_name())));
+    }
+
+    let (settings, _) = parse_settings(settings_value, from)?;
+    let added = PlannedChange::write(
+        to.file_name().to_string(),
+        to.with_settings(&settings_to_value(&settings, to)?)?,
+    );
+    let removed = match from.without_settings()? {
+        Some(fin_str) => PlannedChange::write(from.file_name().to_string(), fin_str),
+        None => PlannedChange::delete(from.file_name().to_string()),
+    };
+
+    Ok(vec![added, removed])
 }
 
-pub fn remove_completely(choices: &ProjectChoices) -> anyhow::Result<()> {
+pub fn remove_completely(choices: &ProjectChoices) -> Result<()> {
     // Remove the entire directory or file from existence
     use Modularity as M;
     let (data_kind, res, file_path) = match choices.modularity {
@@ -99,30 +162,107 @@ pub fn remove_completely(choices: &ProjectChoices) -> anyhow::Result<()> {
     };
 
     match res {
-        Ok(_) => println!("Deleted {data_kind} {file_path}"),
-        Err(_) => println!("{file_path} renamed or already deleted")
+        Ok(_) => output::emit(Event::FileDeleted { path: file_path }),
+        Err(_) => output::emit(Event::Warning {
+            message: format!("{data_kind} {file_path} renamed or already deleted")
+        })
     }
 
-    // Remove the SETTINGS KEY from the file
-    let file_contents = fs::read_to_string(FILE_NAME)?;
-    let mut file_contents: Value = serde_json::from_str(&file_contents)?;
+    let key_removal = plan_key_removal(&choices.name)?;
+    backup_outdated()?;
+    key_removal.apply()?;
+    output::emit(Event::applied(&key_removal));
 
-    let file_contents = file_contents
-        .as_object_mut()
-        .ok_or(anyhow::Error::msg("file_contents is not an object"))?;
+    let lock_removal = lockfile::plan_lock_removal(&choices.name)?;
+    if !lock_removal.is_noop() {
+        lock_removal.apply()?;
+        output::emit(Event::applied(&lock_removal));
+    }
 
-    file_contents
-        .remove(SETTINGS_KEY);
+    Ok(())
+}
 
-    let fin_str = serde_json::to_string_pretty(&file_contents)?;
+/// Copies the settings file aside before it gets overwritten with a newer
+/// schema, so the upgrade can be undone by hand
+pub fn backup_outdated() -> Result<()> {
+    let Some((host, settings_value)) = hosts::locate()? else {
+        return Ok(());
+    };
 
-    if file_contents.keys().len() == 0 {
-        // Nobody else is using it, so it's just taking up extra space
-        fs::remove_file(FILE_NAME)?;
-    } else {
-        // Somebody else is using it, so you can just delete your own part
-        fs::write(FILE_NAME, fin_str)?;
+    let from = migrations::stored_version(&settings_value, host.file_name())?;
+    if from >= SCHEMA_VERSION {
+        return Ok(());
     }
 
+    let backup = format!("{}.v{from}.bak", host.file_name());
+    let contents = fs::read(host.file_name()).at(host.file_name())?;
+    write_atomically(Path::new(&backup), &contents)?;
+    output::emit(Event::ConfigMigrated {
+        path: host.file_name().to_string(),
+        from,
+        to: SCHEMA_VERSION,
+        backup: Some(backup),
+    });
+
     Ok(())
 }
+
+/// Everything `remove_completely` would delete or change
+pub fn plan_removal(choices: &ProjectChoices) -> Result<Vec<PlannedChange>> {
+    let mut changes: Vec<PlannedChange> = choices.find_drift()?
+        .into_iter()
+        .filter(|file| file.state != FileState::Missing)
+        .map(|file| PlannedChange::delete(file.path))
+        .collect();
+
+    changes.push(plan_key_removal(&choices.name)?);
+    changes.push(lockfile::plan_lock_removal(&choices.name)?);
+    changes.retain(|change| !change.is_noop());
+    Ok(changes)
+}
+
+/// What the settings file looks like once the installation called `name` is
+/// removed from it. The SETTINGS KEY goes away together with the last one
+fn plan_key_removal(name: &str) -> Result<PlannedChange> {
+    let (host, settings_value) = hosts::locate()?.ok_or(CopyCrabError::NotInstalled)?;
+    let file_name = host.file_name().to_string();
+
+    let (mut settings, _) = parse_settings(settings_value, host)?;
+    settings.remove(name);
+
+    if !settings.installations.is_empty() {
+        // Other installations are still using it
+        let fin_str = host.with_settings(&settings_to_value(&settings, host)?)?;
+        return Ok(PlannedChange::write(file_name, fin_str));
+    }
+
+    match host.without_settings()? {
+        Some(fin_str) => Ok(PlannedChange::write(file_name, fin_str)),
+        None => Ok(PlannedChange::delete(file_name)),
+    }
+}
+
+/// Upgrades the stored settings to the current schema before reading them.
+/// Also returns the version they were upgraded from
+fn parse_settings(mut settings_value: Value, host: ConfigHost) -> Result<(Settings, Option<u64>)> {
+    let migrated_from = migrations::migrate(&mut settings_value, host.file_name())?;
+
+    // Check if someone else is using copy-paste json as well
+    let settings = serde_json::from_value::<Settings>(settings_value)
+        .map_err(|source| CopyCrabError::InvalidSettings {
+            key: SETTINGS_KEY.to_string(),
+            path: host.file_name().to_string(),
+            source,
+        })?;
+
+    Ok((settings, migrated_from))
+}
+
+fn settings_to_value(settings: &Settings, host: ConfigHost) -> Result<Value> {
+    let mut settings = settings.clone();
+    settings.inline_user_presets();
+
+    let mut settings_value = serde_json::to_value(&settings).map_err(|source| host.parse_error(source))?;
+    settings_value[migrations::VERSION_KEY] = json!(SCHEMA_VERSION);
+    Ok(settings_value)
+}
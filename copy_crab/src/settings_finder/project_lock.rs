@@ -0,0 +1,31 @@
+use std::fs::{File, OpenOptions, TryLockError};
+
+use crate::error::{CopyCrabError, IoContext, Result};
+
+/// Sits next to the settings in the project root. The settings files themselves
+/// can't be locked, since they are replaced by renaming a new file over them
+const PROJECT_LOCK_FILE: &str = ".copy_crab.lock";
+
+/// Keeps other copy_crab runs out of the project until it's dropped
+pub struct ProjectLock {
+    _file: File,
+}
+
+/// Takes the advisory lock on the project root. Fails right away instead of
+/// waiting if another run is holding it, since that one may be stuck in a prompt
+pub fn acquire() -> Result<ProjectLock> {
+    let file = OpenOptions::new()
+        .create(true)
+        .truncate(false)
+        .write(true)
+        .open(PROJECT_LOCK_FILE)
+        .at(PROJECT_LOCK_FILE)?;
+
+    match file.try_lock() {
+        Ok(()) => Ok(ProjectLock { _file: file }),
+        Err(TryLockError::WouldBlock) => Err(CopyCrabError::Locked {
+            path: PROJECT_LOCK_FILE.to_string(),
+        }),
+        Err(TryLockError::Error(err)) => Err(err).at(PROJECT_LOCK_FILE),
+    }
+}
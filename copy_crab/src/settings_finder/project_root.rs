@@ -0,0 +1,127 @@
+use std::env;
+use std::path::{Path, PathBuf};
+use std::sync::OnceLock;
+
+use clap::ValueEnum;
+
+use crate::error::{CopyCrabError, IoContext, Result};
+
+use super::ConfigHost;
+
+/// Files that mark the top of a project. The search for the settings file
+/// doesn't go any higher than a directory containing one of these
+const BOUNDARIES: [&str; 3] = [".git", "deno.json", "package.json"];
+
+// Where copy_crab was started from, relative to the project root
+static INVOKED_FROM: OnceLock<PathBuf> = OnceLock::new();
+
+/// Moves into the project root, so every path copy_crab works with is
+/// relative to it. Without `explicit`, the root is the closest directory
+/// holding the settings file, or else the closest one with a boundary marker
+pub fn enter(explicit: Option<&Path>) -> Result<()> {
+    let cwd = env::current_dir().at(".")?;
+    let root = match explicit {
+        Some(root) => {
+            if !root.is_dir() {
+                return Err(CopyCrabError::InvalidCombination(format!(
+                    "{} is not an existing directory", root.display()
+                )));
+            }
+            cwd.join(root)
+        },
+        None => discover(&cwd),
+    };
+
+    env::set_current_dir(&root).at(&root)?;
+
+    let root = env::current_dir().at(&root)?;
+    let invoked_from = cwd.strip_prefix(&root).unwrap_or(&cwd).to_path_buf();
+    let _ = INVOKED_FROM.set(invoked_from);
+
+    Ok(())
+}
+
+fn discover(start: &Path) -> PathBuf {
+    let mut boundary = None;
+    for dir in start.ancestors() {
+        let has_settings_file = ConfigHost::value_variants()
+            .iter()
+            .filter(|host| host.is_dedicated())
+            .any(|host| dir.join(host.file_name()).is_file());
+        if has_settings_file {
+            return dir.to_path_buf();
+        }
+
+        if BOUNDARIES.iter().any(|marker| dir.join(marker).exists()) {
+            boundary = Some(dir);
+            break;
+        }
+    }
+
+    boundary.unwrap_or(start).to_path_buf()
+}
+
+/// Turns a path the user typed, which is relative to where copy_crab was
+/// started, into one relative to the project root
+pub fn resolve(path: &str) -> String {
+    let invoked_from = INVOKED_FROM.get().map(PathBuf::as_path).unwrap_or(Path::new(""));
+    if Path::new(path).is_absolute() || invoked_from.as_os_str().is_empty() {
+        return path.to_string();
+    }
+
+    invoked_from.join(path).display().to_string()
+}
+
+/// How a directory is stored in the settings: relative to the project root,
+/// with forward slashes, so the file works on every machine. Absolute paths
+/// outside of the project are kept as they are
+pub fn relative(path: &str) -> String {
+    let as_path = Path::new(path);
+    let inside_root = match env::current_dir() {
+        Ok(root) if as_path.is_absolute() => as_path.strip_prefix(&root).ok(),
+        _ => Some(as_path),
+    };
+    let Some(inside_root) = inside_root else {
+        return path.to_string();
+    };
+
+    let normalized = inside_root.to_string_lossy().replace('\\', "/");
+    let mut normalized = normalized.trim_end_matches('/');
+    while let Some(rest) = normalized.strip_prefix("./") {
+        normalized = rest.trim_start_matches('/');
+    }
+
+    match normalized {
+        "" => ".".to_string(),
+        normalized => normalized.to_string(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn relative_normalizes_separators() {
+        assert_eq!(relative("src/lib/"), "src/lib");
+        assert_eq!(relative("./src/lib"), "src/lib");
+        assert_eq!(relative("src\\lib"), "src/lib");
+        assert_eq!(relative("./"), ".");
+        assert_eq!(relative(""), ".");
+    }
+
+    #[test]
+    fn relative_strips_the_project_root() {
+        // Tests run from the crate directory, which stands in for the project root
+        let root = env::current_dir().unwrap();
+
+        assert_eq!(relative(&root.join("src").join("lib").display().to_string()), "src/lib");
+        assert_eq!(relative(&root.display().to_string()), ".");
+    }
+
+    #[test]
+    fn relative_keeps_paths_outside_of_the_project() {
+        let outside = env::temp_dir().join("elsewhere").display().to_string();
+        assert_eq!(relative(&outside), outside);
+    }
+}
@@ -0,0 +1,49 @@
+use std::collections::BTreeMap;
+use std::env;
+use std::fs;
+use std::path::PathBuf;
+
+use crate::error::{CopyCrabError, IoContext, Result};
+use crate::models::{Feature, Modularity, PresetName, Runtime};
+
+use serde::{Serialize, Deserialize};
+
+/// Answers the user gives in every project, read from
+/// `~/.config/copy_crab/config.json`. Every field is optional
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct UserDefaults {
+    pub runtime: Option<Runtime>,
+    /// Relative to the project root
+    pub chosen_directory: Option<String>,
+    pub preset: Option<PresetName>,
+    pub modularity: Option<Modularity>,
+    /// Presets available in every project
+    #[serde(default)]
+    pub presets: BTreeMap<String, Vec<Feature>>,
+}
+
+/// Where the user-level config lives. Follows `XDG_CONFIG_HOME`, falling back
+/// to `~/.config` (or `%APPDATA%` on Windows)
+pub fn path() -> Option<PathBuf> {
+    let config_home = env::var_os("XDG_CONFIG_HOME")
+        .filter(|dir| !dir.is_empty())
+        .map(PathBuf::from)
+        .or_else(|| env::var_os("HOME").map(|home| PathBuf::from(home).join(".config")))
+        .or_else(|| env::var_os("APPDATA").map(PathBuf::from))?;
+
+    Some(config_home.join("copy_crab").join("config.json"))
+}
+
+/// The user's defaults. Having none is fine, a broken file is not.
+/// Comments are allowed, like in `copy-paste.json`
+pub fn load() -> Result<UserDefaults> {
+    let Some(path) = path().filter(|path| path.is_file()) else {
+        return Ok(UserDefaults::default());
+    };
+
+    let file_contents = fs::read_to_string(&path).at(&path)?;
+    serde_json::from_str(&super::jsonc::strip(&file_contents)).map_err(|source| CopyCrabError::ConfigParse {
+        path: path.display().to_string(),
+        source,
+    })
+}
@@ -1,5 +1,5 @@
-pub const CORE_FUNCTIONS: &'static str = include_str!("../ts/core_functions.ts");
-pub const EXAMPLE: &'static str = include_str!("../ts/example.ts");
-pub const OPTION: &'static str = include_str!("../ts/option.ts");
-pub const PARSERS: &'static str = include_str!("../ts/parsers.ts");
-pub const RESULT: &'static str = include_str!("../ts/result.ts");
+pub const CORE_FUNCTIONS: &str = include_str!("../ts/core_functions.ts");
+pub const EXAMPLE: &str = include_str!("../ts/example.ts");
+pub const OPTION: &str = include_str!("../ts/option.ts");
+pub const PARSERS: &str = include_str!("../ts/parsers.ts");
+pub const RESULT: &str = include_str!("../ts/result.ts");